@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use http_server_starter_rust::response::Response;
+use std::path::Path;
+use tokio::{fs::File, io::AsyncReadExt, runtime::Runtime};
+
+/// As large as a file served under `/files/` is likely to get
+const FILE_SIZE: usize = 100 * 1024 * 1024;
+
+/// How files used to be sent, read into memory in full first
+async fn read_whole(path: &Path) {
+    let mut file = File::open(path).await.expect("open file");
+    let mut body = Vec::new();
+    file.read_to_end(&mut body).await.expect("read file");
+
+    let response = Response::builder().body(body).build();
+    response
+        .write_to(&mut tokio::io::sink())
+        .await
+        .expect("write response");
+}
+
+async fn stream(path: &Path) {
+    let file = File::open(path).await.expect("open file");
+
+    let response = Response::builder().chunked_body(file).build();
+    response
+        .write_to(&mut tokio::io::sink())
+        .await
+        .expect("write response");
+}
+
+fn write_file(c: &mut Criterion) {
+    let temp = tempfile::tempdir().expect("create dir");
+    let path = temp.path().join("file");
+    std::fs::write(&path, vec![b'a'; FILE_SIZE]).expect("write file");
+
+    let runtime = Runtime::new().expect("build runtime");
+
+    let mut group = c.benchmark_group("write_100_mib_file");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+
+    group.bench_function("read_whole", |b| {
+        b.iter(|| runtime.block_on(read_whole(black_box(&path))))
+    });
+    group.bench_function("stream", |b| {
+        b.iter(|| runtime.block_on(stream(black_box(&path))))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, write_file);
+criterion_main!(benches);
@@ -0,0 +1,106 @@
+//! Throughput of the request parsers, each is expected to stay well under 10 µs per call
+//!
+//! Run with `cargo bench --features bench --bench parsing`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use http_server_starter_rust::{
+    header::{parse_header_value, Header},
+    request::{Method, RequestLine},
+};
+use std::time::Duration;
+
+const METHODS: [&str; 4] = ["GET", "POST", "PUT", "DELETE"];
+
+/// Every header with a variant of its own, `Custom` is benched separately
+const KNOWN_HEADERS: [Header; 46] = [
+    Header::UserAgent,
+    Header::ContentType,
+    Header::ContentLength,
+    Header::TransferEncoding,
+    Header::AcceptEncoding,
+    Header::ContentEncoding,
+    Header::Connection,
+    Header::Origin,
+    Header::AccessControlRequestMethod,
+    Header::AccessControlRequestHeaders,
+    Header::AccessControlAllowOrigin,
+    Header::AccessControlAllowMethods,
+    Header::AccessControlAllowHeaders,
+    Header::ETag,
+    Header::IfNoneMatch,
+    Header::RetryAfter,
+    Header::ContentDisposition,
+    Header::Cookie,
+    Header::SetCookie,
+    Header::Authorization,
+    Header::WwwAuthenticate,
+    Header::CacheControl,
+    Header::Expires,
+    Header::Date,
+    Header::LastModified,
+    Header::Range,
+    Header::ContentRange,
+    Header::AcceptRanges,
+    Header::Upgrade,
+    Header::SecWebSocketKey,
+    Header::SecWebSocketAccept,
+    Header::SecWebSocketVersion,
+    Header::Accept,
+    Header::IfModifiedSince,
+    Header::Location,
+    Header::Vary,
+    Header::Host,
+    Header::Expect,
+    Header::RequestId,
+    Header::Via,
+    Header::Allow,
+    Header::SecFetchSite,
+    Header::SecFetchMode,
+    Header::SecFetchDest,
+    Header::Server,
+    Header::ServerTiming,
+];
+
+fn method(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Method::parse");
+
+    for method in METHODS {
+        // Streaming parsers need to see what follows the method
+        let input = format!("{method} / HTTP/1.1\r\n");
+
+        group.bench_function(method, |b| b.iter(|| Method::parse(black_box(&input))));
+    }
+
+    group.finish();
+}
+
+fn request_line(c: &mut Criterion) {
+    let input = "GET /echo/hello?greeting=1 HTTP/1.1";
+
+    c.bench_function("RequestLine::parse", |b| {
+        b.iter(|| RequestLine::parse(black_box(input)))
+    });
+}
+
+fn header_value(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_header_value");
+
+    // Enough to tell a regression apart from noise without taking minutes for every header
+    group.warm_up_time(Duration::from_millis(500));
+    group.measurement_time(Duration::from_secs(1));
+
+    let custom = Header::Custom("X-Custom-Header".to_owned());
+
+    for header in KNOWN_HEADERS.iter().chain([&custom]) {
+        let input = format!("{header}: text/plain; charset=utf-8");
+
+        group.bench_function(header.to_string(), |b| {
+            b.iter(|| parse_header_value(black_box(&input)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, method, request_line, header_value);
+criterion_main!(benches);
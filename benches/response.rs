@@ -0,0 +1,47 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use http_server_starter_rust::{header::Header, response::Response};
+use std::fmt::Write;
+
+/// A plain text response of about 200 bytes, like most answers to the challenge routes
+fn typical_response() -> Response {
+    Response::builder()
+        .header(Header::ContentType, "text/plain")
+        .header(Header::CacheControl, "no-cache")
+        .header(Header::RequestId, "0b3c2a52-5d3e-4f0c-9d5a-2f8e4c1b7a90")
+        .body("a".repeat(64))
+        .build()
+}
+
+/// How responses used to be written, formatting the head into a new string
+fn format_head(response: &Response) -> String {
+    let mut head = String::new();
+    let _ = write!(head, "HTTP/1.1 {}\r\n", response.status);
+
+    for (header, value) in &response.headers {
+        let _ = write!(head, "{}: {}\r\n", header, value);
+    }
+
+    head.push_str("\r\n");
+    head
+}
+
+fn serialize(c: &mut Criterion) {
+    let response = typical_response();
+
+    c.bench_function("format_head", |b| {
+        // The body was written separately, straight from the response
+        b.iter(|| black_box(format_head(black_box(&response))))
+    });
+
+    let mut buf = Vec::with_capacity(1024);
+    c.bench_function("serialize_into", |b| {
+        b.iter(|| {
+            buf.clear();
+            black_box(&response).serialize_into(&mut buf);
+            black_box(buf.len())
+        })
+    });
+}
+
+criterion_group!(benches, serialize);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+#![no_main]
+
+use http_server_starter_rust::header::parse_header_value;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|line: &str| {
+    let Ok((_, (header, value))) = parse_header_value(line) else {
+        return;
+    };
+
+    // Whatever is accepted must be safe to send back in a response
+    let name = header.to_string();
+    assert!(!name.is_empty() && !name.contains([' ', ':', '\r', '\n']));
+    assert!(!value.contains(['\r', '\n', '\0']));
+});
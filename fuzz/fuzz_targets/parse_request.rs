@@ -0,0 +1,16 @@
+#![no_main]
+
+use http_server_starter_rust::{config::ServerConfig, request::Request};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("build runtime");
+
+    let mut reader = data;
+    let config = ServerConfig::default();
+
+    // Any input may be rejected, but none may panic
+    let _ = runtime.block_on(Request::parse_from_reader(&mut reader, &config));
+});
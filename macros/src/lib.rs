@@ -0,0 +1,217 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use std::cmp::Ordering;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Expr, Ident, LitStr, Token,
+};
+
+/// Methods a route can be registered for, the variants of `Method`
+const METHODS: [&str; 13] = [
+    "GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "TRACE", "CONNECT", "PROPFIND",
+    "MKCOL", "COPY", "MOVE",
+];
+
+/// `METHOD "/pattern" => handler`
+struct RouteDef {
+    method: Ident,
+    pattern: LitStr,
+    handler: Expr,
+}
+
+impl Parse for RouteDef {
+    fn parse(input: ParseStream) -> syn::Result<RouteDef> {
+        let method: Ident = input.parse()?;
+        let pattern = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let handler = input.parse()?;
+
+        if !METHODS.contains(&method.to_string().as_str()) {
+            return Err(syn::Error::new(
+                method.span(),
+                format!(
+                    "unknown method `{method}`, expected one of {}",
+                    METHODS.join(", ")
+                ),
+            ));
+        }
+
+        Ok(RouteDef {
+            method,
+            pattern,
+            handler,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param,
+}
+
+/// The segments of `pattern`, parameters are all the same whatever their name
+fn shape(pattern: &str) -> Vec<Segment> {
+    pattern
+        .split('/')
+        .skip(1)
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(_) => Segment::Param,
+            None => Segment::Literal(segment.to_owned()),
+        })
+        .collect()
+}
+
+/// Orders the more specific of two patterns first
+///
+/// Segments are compared from the left, at the first one that differs a literal beats a
+/// parameter. A pattern that goes on after the other ends beats it.
+fn specificity(a: &[Segment], b: &[Segment]) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        match (a, b) {
+            (Segment::Literal(_), Segment::Param) => return Ordering::Less,
+            (Segment::Param, Segment::Literal(_)) => return Ordering::Greater,
+            _ => {}
+        }
+    }
+
+    b.len().cmp(&a.len())
+}
+
+/// Whether two patterns match exactly the same paths, so neither can be picked over the other
+fn conflicts(a: &[Segment], b: &[Segment]) -> bool {
+    a == b
+}
+
+/// Builds a `Router` from `METHOD "/pattern" => handler` routes, refusing to compile when two
+/// routes for the same method would match the same paths
+///
+/// ```
+/// # use http_server_starter_rust::{
+/// #     request::Request,
+/// #     response::{Response, Status},
+/// #     router::{routes, Params},
+/// # };
+/// # async fn get_user(_: Request, _: Params) -> Response { Response::empty(Status::Ok) }
+/// # async fn get_me(_: Request, _: Params) -> Response { Response::empty(Status::Ok) }
+/// # async fn create_user(_: Request, _: Params) -> Response { Response::empty(Status::Created) }
+/// let router = routes! {
+///     GET "/users/:id" => get_user,
+///     GET "/users/me" => get_me,
+///     POST "/users" => create_user,
+/// };
+/// ```
+///
+/// Routes are registered from the most to the least specific, so the order they're written
+/// in doesn't matter:
+///
+/// - Segments are compared from the left, at the first one that differs a literal segment
+///   beats a parameter, so `/users/me` comes before `/users/:id` and `/a/:b` before `/:a/b`
+/// - A longer pattern beats a shorter one it starts with, so `/users/:id` comes before
+///   `/users`
+/// - Routes that tie keep the order they're written in
+///
+/// Two routes only tie when they are the same pattern with parameters named differently,
+/// e.g. `/users/:id` and `/users/:name`, which is an error for the same method:
+///
+/// ```compile_fail
+/// # use http_server_starter_rust::{
+/// #     request::Request,
+/// #     response::{Response, Status},
+/// #     router::{routes, Params},
+/// # };
+/// # async fn get_user(_: Request, _: Params) -> Response { Response::empty(Status::Ok) }
+/// let router = routes! {
+///     GET "/users/:id" => get_user,
+///     GET "/users/:name" => get_user,
+/// };
+/// ```
+#[proc_macro]
+pub fn routes(input: TokenStream) -> TokenStream {
+    let routes = parse_macro_input!(input with Punctuated::<RouteDef, Token![,]>::parse_terminated);
+    let mut routes: Vec<_> = routes.into_iter().collect();
+
+    let mut errors: Option<syn::Error> = None;
+
+    for (index, route) in routes.iter().enumerate() {
+        let pattern = route.pattern.value();
+
+        let error = if !pattern.starts_with('/') {
+            syn::Error::new(route.pattern.span(), "patterns must start with `/`")
+        } else if let Some(earlier) = routes[..index].iter().find(|earlier| {
+            earlier.method == route.method
+                && conflicts(&shape(&earlier.pattern.value()), &shape(&pattern))
+        }) {
+            syn::Error::new(
+                route.pattern.span(),
+                format!(
+                    "route `{} {pattern}` conflicts with `{} {}`, both match the same paths",
+                    route.method,
+                    earlier.method,
+                    earlier.pattern.value(),
+                ),
+            )
+        } else {
+            continue;
+        };
+
+        match &mut errors {
+            Some(errors) => errors.combine(error),
+            None => errors = Some(error),
+        }
+    }
+
+    if let Some(errors) = errors {
+        // A block, so several errors still make an expression
+        let errors = errors.to_compile_error();
+        return quote!({ #errors }).into();
+    }
+
+    // Stable, so ties keep the order they were written in
+    routes.sort_by(|a, b| specificity(&shape(&a.pattern.value()), &shape(&b.pattern.value())));
+
+    let routes = routes.iter().map(
+        |RouteDef {
+             method,
+             pattern,
+             handler,
+         }| {
+            quote! {
+                .route(::http_server_starter_rust::request::Method::#method, #pattern, #handler)
+            }
+        },
+    );
+
+    quote! {
+        ::http_server_starter_rust::router::Router::new() #(#routes)*
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_specificity() {
+        let mut patterns = vec!["/users", "/users/:id", "/:a/b", "/users/me", "/a/:b", "/"];
+        patterns.sort_by(|a, b| specificity(&shape(a), &shape(b)));
+
+        assert_eq!(
+            patterns,
+            ["/users/me", "/users/:id", "/a/:b", "/users", "/", "/:a/b"]
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        assert!(conflicts(&shape("/users/:id"), &shape("/users/:name")));
+        assert!(conflicts(&shape("/users"), &shape("/users")));
+
+        assert!(!conflicts(&shape("/users/:id"), &shape("/users/me")));
+        assert!(!conflicts(&shape("/:a/b"), &shape("/a/:b")));
+        assert!(!conflicts(&shape("/users"), &shape("/users/")));
+    }
+}
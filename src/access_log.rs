@@ -0,0 +1,128 @@
+use crate::{
+    middleware::{Middleware, Next},
+    request::Request,
+    response::Response,
+    router::BoxFuture,
+};
+use chrono::{DateTime, Local, TimeZone};
+use std::{io, path::Path};
+use tokio::{
+    fs::OpenOptions,
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+    sync::Mutex,
+};
+
+type LogWriter = BufWriter<Box<dyn AsyncWrite + Send + Unpin>>;
+
+/// Writes one line per request in the Common Log Format used by Apache and nginx
+pub struct AccessLog {
+    writer: Mutex<LogWriter>,
+}
+
+impl AccessLog {
+    pub fn new(writer: impl AsyncWrite + Send + Unpin + 'static) -> AccessLog {
+        let writer: Box<dyn AsyncWrite + Send + Unpin> = Box::new(writer);
+
+        AccessLog {
+            writer: Mutex::new(BufWriter::new(writer)),
+        }
+    }
+
+    pub fn stdout() -> AccessLog {
+        AccessLog::new(tokio::io::stdout())
+    }
+
+    /// Appends to the file at `path`, creating it if needed
+    pub async fn open(path: &Path) -> io::Result<AccessLog> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+
+        Ok(AccessLog::new(file))
+    }
+
+    async fn write_line(&self, line: &str) -> io::Result<()> {
+        let mut writer = self.writer.lock().await;
+
+        writer.write_all(line.as_bytes()).await?;
+        writer.flush().await
+    }
+}
+
+/// `<remote-ip> - - [<time>] "<METHOD> <path> HTTP/1.1" <status> <bytes>`, `-` for unknowns
+fn format_line<Tz>(request: &Request, time: &DateTime<Tz>, response: &Response) -> String
+where
+    Tz: TimeZone,
+    Tz::Offset: std::fmt::Display,
+{
+    let remote_ip = request
+        .client_ip()
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "-".to_owned());
+
+    // The size of a streamed body isn't known until it has been sent
+    let bytes = response
+        .body
+        .as_bytes()
+        .map(|body| body.len().to_string())
+        .unwrap_or_else(|| "-".to_owned());
+
+    format!(
+        "{remote_ip} - - [{}] \"{} {} HTTP/1.1\" {} {bytes}\n",
+        time.format("%d/%b/%Y:%H:%M:%S %z"),
+        request.method,
+        request.path,
+        response.status.code(),
+    )
+}
+
+impl Middleware for AccessLog {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+        Box::pin(async move {
+            let time = Local::now();
+
+            // Only the request line is logged, so the body doesn't have to be kept around
+            let mut logged = Request::new(request.method, request.path.clone());
+            logged.remote_addr = request.remote_addr;
+            logged.forwarded_for = request.forwarded_for;
+
+            let response = next.run(request).await;
+
+            let line = format_line(&logged, &time, &response);
+            if let Err(err) = self.write_line(&line).await {
+                tracing::warn!("error writing access log: {err}");
+            }
+
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{request::Method, response::Status};
+    use chrono::FixedOffset;
+
+    #[test]
+    fn test_format_line() {
+        let mut request = Request::new(Method::GET, "/echo/abc");
+        request.remote_addr = Some("127.0.0.1:50962".parse().expect("parse addr"));
+
+        let time = FixedOffset::west_opt(7 * 3600)
+            .and_then(|offset| offset.with_ymd_and_hms(2000, 10, 10, 13, 55, 36).single())
+            .expect("valid time");
+
+        let response = Response::builder()
+            .status(Status::NotFound)
+            .body("abc")
+            .build();
+
+        assert_eq!(
+            format_line(&request, &time, &response),
+            "127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] \"GET /echo/abc HTTP/1.1\" 404 3\n"
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{
+    error::HttpError,
+    header::Header,
+    request::Request,
+    response::{Response, Status},
+    router::{routes, Params, Router},
+};
+
+/// The routes of the challenge: `/`, `/user-agent` and `/echo/:text`
+pub fn router() -> Router {
+    routes! {
+        GET "/" => handle_root,
+        GET "/user-agent" => handle_user_agent,
+        GET "/echo/:text" => handle_echo,
+    }
+}
+
+async fn handle_root(_request: Request, _params: Params) -> Response {
+    Response::empty(Status::Ok)
+}
+
+async fn handle_user_agent(request: Request, _params: Params) -> Result<Response, HttpError> {
+    let user_agent = request
+        .headers
+        .get_first(&Header::UserAgent)
+        .ok_or_else(|| HttpError::BadRequest("missing User-Agent header".to_owned()))?;
+
+    Ok(Response::builder()
+        .header(Header::ContentType, "text/plain")
+        .body(user_agent)
+        .compress()
+        .build())
+}
+
+async fn handle_echo(_request: Request, params: Params) -> Response {
+    let echo = params.get("text").unwrap_or_default();
+
+    Response::builder()
+        .header(Header::ContentType, "text/plain")
+        .body(echo)
+        .compress()
+        .build()
+}
@@ -0,0 +1,839 @@
+use crate::{
+    header::Header,
+    middleware::{Middleware, Next},
+    request::Request,
+    response::{Response, Status},
+    router::BoxFuture,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped_transform, take_while1},
+    character::complete::{anychar, char, none_of, space0},
+    combinator::{all_consuming, map, opt},
+    multi::separated_list1,
+    sequence::{delimited, separated_pair, tuple},
+    IResult,
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// The credentials of an `Authorization` header using `scheme`, e.g. `Basic` or `Bearer`
+fn credentials<'a>(request: &'a Request, scheme: &str) -> Option<&'a str> {
+    let authorization = request.headers.get_first(&Header::Authorization)?;
+    let (request_scheme, credentials) = authorization.split_once(' ')?;
+
+    request_scheme
+        .eq_ignore_ascii_case(scheme)
+        .then(|| credentials.trim())
+}
+
+/// Compares without returning early, so the time taken doesn't reveal how much of a secret matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Requires `Authorization: Basic` credentials matching a username and password
+#[derive(Debug, Clone)]
+pub struct BasicAuth {
+    /// Shown by browsers when asking for credentials
+    pub realm: String,
+    /// Passwords by username
+    pub credentials: HashMap<String, String>,
+}
+
+impl BasicAuth {
+    /// Whether the request carries valid credentials
+    pub fn is_authorized(&self, request: &Request) -> bool {
+        let Some(decoded) = credentials(request, "Basic")
+            .and_then(|encoded| STANDARD.decode(encoded).ok())
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+        else {
+            return false;
+        };
+
+        let Some((username, password)) = decoded.split_once(':') else {
+            return false;
+        };
+
+        self.credentials
+            .get(username)
+            .is_some_and(|expected| constant_time_eq(expected.as_bytes(), password.as_bytes()))
+    }
+
+    fn unauthorized(&self) -> Response {
+        Response::builder()
+            .status(Status::Unauthorized)
+            .header(
+                Header::WwwAuthenticate,
+                format!("Basic realm=\"{}\"", self.realm),
+            )
+            .build()
+    }
+}
+
+impl Middleware for BasicAuth {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+        Box::pin(async move {
+            if !self.is_authorized(&request) {
+                return self.unauthorized();
+            }
+
+            next.run(request).await
+        })
+    }
+}
+
+/// The hash a [`DigestAuth`] challenge asks clients to use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    /// Weak, but the only one many clients support
+    #[default]
+    Md5,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    /// The name in the `algorithm` parameter
+    fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    /// The lowercase hex digest of `data`
+    fn hash(&self, data: &str) -> String {
+        match self {
+            DigestAlgorithm::Md5 => format!("{:x}", Md5::digest(data)),
+            DigestAlgorithm::Sha256 => format!("{:x}", Sha256::digest(data)),
+        }
+    }
+}
+
+/// Requires `Authorization: Digest` credentials matching a username and password, following
+/// RFC 7616 with `qop=auth`
+///
+/// Every challenge has a fresh nonce signed by the server, which can be used for
+/// `nonce_lifetime` as long as each request counts the nonce count `nc` up, so captured
+/// requests can't be replayed. Answering an expired nonce or repeating a count with the right
+/// password gets a challenge with `stale=true`, so clients retry with the new nonce without
+/// asking the user again.
+///
+/// Challenging keeps no state, only nonces that were answered are remembered, at most
+/// [`MAX_ANSWERED_NONCES`] of them.
+#[derive(Debug, Clone)]
+pub struct DigestAuth {
+    /// Shown by browsers when asking for credentials, and part of the hashed credentials
+    pub realm: String,
+    /// Passwords by username
+    pub credentials: HashMap<String, String>,
+    pub algorithm: DigestAlgorithm,
+    pub nonce_lifetime: Duration,
+    /// Signs nonces, so ones the server didn't issue are recognized without storing any
+    secret: [u8; 32],
+    /// Shared between clones
+    answered: Arc<Mutex<AnsweredNonces>>,
+}
+
+/// Nonces remembered by [`DigestAuth`] before the oldest are forgotten
+pub const MAX_ANSWERED_NONCES: usize = 4096;
+
+/// The highest `nc` answered on each nonce
+#[derive(Debug, Default)]
+struct AnsweredNonces {
+    /// `(issued, highest nc)` by nonce
+    nonces: HashMap<String, (u64, u64)>,
+    /// Nonces issued at or before this were forgotten to make room, so a nonce from then that
+    /// isn't remembered may have been answered already
+    forgotten_until: Option<u64>,
+}
+
+impl AnsweredNonces {
+    /// Records `nc` for `nonce`, false if it isn't higher than a count already answered
+    ///
+    /// Making room for a new nonce drops the expired ones, then the oldest.
+    fn answer(
+        &mut self,
+        nonce: &str,
+        issued: u64,
+        nc: u64,
+        is_expired: impl Fn(u64) -> bool,
+        max: usize,
+    ) -> bool {
+        if let Some((_, highest)) = self.nonces.get_mut(nonce) {
+            if nc <= *highest {
+                return false;
+            }
+
+            *highest = nc;
+            return true;
+        }
+
+        if self.forgotten_until.is_some_and(|until| issued <= until) {
+            return false;
+        }
+
+        if self.nonces.len() >= max {
+            self.nonces.retain(|_, (issued, _)| !is_expired(*issued));
+        }
+
+        while self.nonces.len() >= max {
+            let Some((oldest, oldest_issued)) = self
+                .nonces
+                .iter()
+                .min_by_key(|(_, (issued, _))| *issued)
+                .map(|(nonce, (issued, _))| (nonce.clone(), *issued))
+            else {
+                break;
+            };
+
+            self.nonces.remove(&oldest);
+            self.forgotten_until = self.forgotten_until.max(Some(oldest_issued));
+        }
+
+        self.nonces.insert(nonce.to_owned(), (issued, nc));
+        true
+    }
+}
+
+/// Whether a `Digest` authorization is accepted
+#[derive(Debug, PartialEq, Eq)]
+enum DigestOutcome {
+    Authorized,
+    /// The credentials are right, but the nonce expired or was already used
+    Stale,
+    Unauthorized,
+}
+
+impl DigestAuth {
+    /// Challenges with MD5 and nonces lasting 5 minutes
+    pub fn new(realm: impl Into<String>, credentials: HashMap<String, String>) -> DigestAuth {
+        DigestAuth {
+            realm: realm.into(),
+            credentials,
+            algorithm: DigestAlgorithm::default(),
+            nonce_lifetime: Duration::from_secs(5 * 60),
+            secret: random_secret(),
+            answered: Arc::default(),
+        }
+    }
+
+    /// `<issued>.<random>.<signature>`, with the time issued in hex seconds
+    fn issue_nonce(&self) -> String {
+        let payload = format!("{:x}.{}", unix_time(), Uuid::new_v4().simple());
+        let signature = self.sign_nonce(&payload);
+
+        format!("{payload}.{signature}")
+    }
+
+    fn sign_nonce(&self, payload: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("hmac key of any length");
+        mac.update(payload.as_bytes());
+
+        URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    /// When `nonce` was issued, `None` if this server didn't issue it
+    fn issued_at(&self, nonce: &str) -> Option<u64> {
+        let (payload, signature) = nonce.rsplit_once('.')?;
+
+        if !constant_time_eq(self.sign_nonce(payload).as_bytes(), signature.as_bytes()) {
+            return None;
+        }
+
+        let (issued, _) = payload.split_once('.')?;
+        u64::from_str_radix(issued, 16).ok()
+    }
+
+    fn is_expired(&self, issued: u64, now: u64) -> bool {
+        now.saturating_sub(issued) >= self.nonce_lifetime.as_secs()
+    }
+
+    fn verify(&self, request: &Request) -> DigestOutcome {
+        let Some(params) = credentials(request, "Digest").and_then(|params| {
+            all_consuming(digest_params)(params)
+                .ok()
+                .map(|(_, params)| params.into_iter().collect::<HashMap<_, _>>())
+        }) else {
+            return DigestOutcome::Unauthorized;
+        };
+
+        let param = |name: &str| params.get(name).map(String::as_str);
+
+        let (Some(username), Some(nonce), Some(uri), Some(response), Some(nc), Some(cnonce)) = (
+            param("username"),
+            param("nonce"),
+            param("uri"),
+            param("response"),
+            param("nc"),
+            param("cnonce"),
+        ) else {
+            return DigestOutcome::Unauthorized;
+        };
+
+        let algorithm = param("algorithm").unwrap_or("MD5");
+        let uri_path = uri.split_once('?').map_or(uri, |(path, _)| path);
+
+        // The response is only bound to what the client says it's requesting
+        if param("realm") != Some(&self.realm)
+            || param("qop") != Some("auth")
+            || !algorithm.eq_ignore_ascii_case(self.algorithm.name())
+            || uri_path != request.path
+        {
+            return DigestOutcome::Unauthorized;
+        }
+
+        let Some(password) = self.credentials.get(username) else {
+            return DigestOutcome::Unauthorized;
+        };
+
+        let ha1 = self
+            .algorithm
+            .hash(&format!("{username}:{}:{password}", self.realm));
+        let ha2 = self.algorithm.hash(&format!("{}:{uri}", request.method));
+        let expected = self
+            .algorithm
+            .hash(&format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}"));
+
+        if !constant_time_eq(
+            expected.as_bytes(),
+            response.to_ascii_lowercase().as_bytes(),
+        ) {
+            return DigestOutcome::Unauthorized;
+        }
+
+        let Ok(nc) = u64::from_str_radix(nc, 16) else {
+            return DigestOutcome::Unauthorized;
+        };
+
+        let now = unix_time();
+        let Some(issued) = self
+            .issued_at(nonce)
+            .filter(|issued| !self.is_expired(*issued, now))
+        else {
+            return DigestOutcome::Stale;
+        };
+
+        // A count that isn't higher than the last is a replay
+        let fresh = self
+            .answered
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .answer(
+                nonce,
+                issued,
+                nc,
+                |issued| self.is_expired(issued, now),
+                MAX_ANSWERED_NONCES,
+            );
+
+        if !fresh {
+            return DigestOutcome::Stale;
+        }
+
+        DigestOutcome::Authorized
+    }
+
+    fn unauthorized(&self, stale: bool) -> Response {
+        let mut challenge = format!(
+            "Digest realm=\"{}\", qop=\"auth\", algorithm={}, nonce=\"{}\"",
+            self.realm,
+            self.algorithm.name(),
+            self.issue_nonce()
+        );
+
+        if stale {
+            challenge.push_str(", stale=true");
+        }
+
+        Response::builder()
+            .status(Status::Unauthorized)
+            .header(Header::WwwAuthenticate, challenge)
+            .build()
+    }
+}
+
+impl Middleware for DigestAuth {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+        Box::pin(async move {
+            match self.verify(&request) {
+                DigestOutcome::Authorized => next.run(request).await,
+                DigestOutcome::Stale => self.unauthorized(true),
+                DigestOutcome::Unauthorized => self.unauthorized(false),
+            }
+        })
+    }
+}
+
+fn random_secret() -> [u8; 32] {
+    let mut secret = [0; 32];
+    secret[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    secret[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+
+    secret
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn token(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))(input)
+}
+
+/// The comma-separated `name=value` parameters of `Digest` credentials, values may be quoted
+fn digest_params(input: &str) -> IResult<&str, Vec<(String, String)>> {
+    let quoted = delimited(
+        char('"'),
+        // Fails on an empty string
+        opt(escaped_transform(none_of("\\\""), '\\', anychar)),
+        char('"'),
+    );
+    let value = alt((
+        map(quoted, Option::unwrap_or_default),
+        map(token, str::to_owned),
+    ));
+    let param = separated_pair(map(token, str::to_ascii_lowercase), char('='), value);
+
+    delimited(
+        space0,
+        separated_list1(tuple((space0, char(','), space0)), param),
+        space0,
+    )(input)
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AuthError {
+    #[error("missing bearer token")]
+    MissingToken,
+
+    #[error("malformed token")]
+    Malformed,
+
+    #[error("unsupported algorithm {0:?}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    #[error("token expired")]
+    Expired,
+}
+
+/// Checks a bearer token and extracts what it claims about the client
+pub trait TokenValidator: Send + Sync + 'static {
+    type Claims: Send + Sync + 'static;
+
+    fn validate(&self, token: &str) -> Result<Self::Claims, AuthError>;
+}
+
+/// Validates JWTs signed with HS256 using a shared secret, rejecting them once `exp` has passed
+pub struct HmacValidator {
+    secret: Vec<u8>,
+}
+
+impl HmacValidator {
+    pub fn new(secret: impl Into<Vec<u8>>) -> HmacValidator {
+        HmacValidator {
+            secret: secret.into(),
+        }
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        // HMAC accepts keys of any length
+        Hmac::new_from_slice(&self.secret).expect("hmac key of any length")
+    }
+}
+
+/// Decodes a base64url JWT segment as JSON
+fn decode_segment(segment: &str) -> Result<Value, AuthError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(segment)
+        .map_err(|_| AuthError::Malformed)?;
+
+    serde_json::from_slice(&bytes).map_err(|_| AuthError::Malformed)
+}
+
+impl TokenValidator for HmacValidator {
+    /// The JSON payload of the token
+    type Claims = Value;
+
+    fn validate(&self, token: &str) -> Result<Value, AuthError> {
+        let mut segments = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) = (
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) else {
+            return Err(AuthError::Malformed);
+        };
+
+        // Only HS256 is accepted, tokens don't get to pick a weaker algorithm such as `none`
+        let algorithm = decode_segment(header)?
+            .get("alg")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .unwrap_or_default();
+        if algorithm != "HS256" {
+            return Err(AuthError::UnsupportedAlgorithm(algorithm));
+        }
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| AuthError::Malformed)?;
+
+        let mut mac = self.mac();
+        mac.update(format!("{header}.{payload}").as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| AuthError::InvalidSignature)?;
+
+        let claims = decode_segment(payload)?;
+
+        if let Some(expires) = claims.get("exp").and_then(Value::as_u64) {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+
+            if now >= expires {
+                return Err(AuthError::Expired);
+            }
+        }
+
+        Ok(claims)
+    }
+}
+
+/// Requires an `Authorization: Bearer` token accepted by `V`, handing its claims to the
+/// handler in [`Request::extensions`]
+pub struct BearerAuth<V> {
+    pub validator: V,
+}
+
+impl<V: TokenValidator> BearerAuth<V> {
+    pub fn new(validator: V) -> BearerAuth<V> {
+        BearerAuth { validator }
+    }
+
+    pub fn authorize(&self, request: &Request) -> Result<V::Claims, AuthError> {
+        let token = credentials(request, "Bearer").ok_or(AuthError::MissingToken)?;
+
+        self.validator.validate(token)
+    }
+}
+
+impl<V: TokenValidator> Middleware for BearerAuth<V> {
+    fn call<'a>(&'a self, mut request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+        Box::pin(async move {
+            let claims = match self.authorize(&request) {
+                Ok(claims) => claims,
+                Err(err) => {
+                    tracing::debug!("unauthorized: {err}");
+
+                    return Response::builder()
+                        .status(Status::Unauthorized)
+                        .header(Header::WwwAuthenticate, "Bearer")
+                        .build();
+                }
+            };
+
+            request.extensions.insert(claims);
+
+            next.run(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{request::Method, router::Router};
+
+    fn router() -> Router {
+        let auth = BasicAuth {
+            realm: "admin".to_owned(),
+            credentials: HashMap::from([("alice".to_owned(), "secret".to_owned())]),
+        };
+
+        Router::new()
+            .get("/", |_, _| async { Response::empty(Status::Ok) })
+            .layer(auth)
+    }
+
+    fn request(authorization: Option<&str>) -> Request {
+        let mut request = Request::new(Method::GET, "/");
+
+        if let Some(authorization) = authorization {
+            request.headers.append(Header::Authorization, authorization);
+        }
+
+        request
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth() {
+        let router = router();
+
+        let response = router.handle(request(None)).await;
+        assert_eq!(response.status, Status::Unauthorized);
+        assert_eq!(
+            response.headers.get(&Header::WwwAuthenticate),
+            Some(&"Basic realm=\"admin\"".to_owned())
+        );
+
+        // alice:secret
+        let response = router.handle(request(Some("Basic YWxpY2U6c2VjcmV0"))).await;
+        assert_eq!(response.status, Status::Ok);
+
+        // alice:wrong
+        let response = router.handle(request(Some("Basic YWxpY2U6d3Jvbmc="))).await;
+        assert_eq!(response.status, Status::Unauthorized);
+
+        let response = router.handle(request(Some("Basic not base64"))).await;
+        assert_eq!(response.status, Status::Unauthorized);
+    }
+
+    /// The credentials of the examples in RFC 7616 section 3.9.1
+    fn rfc_digest_auth(algorithm: DigestAlgorithm, password: &str) -> DigestAuth {
+        DigestAuth {
+            algorithm,
+            ..DigestAuth::new(
+                "http-auth@example.org",
+                HashMap::from([("Mufasa".to_owned(), password.to_owned())]),
+            )
+        }
+    }
+
+    fn digest_request(path: &str, params: &str) -> Request {
+        let mut request = Request::new(Method::GET, path);
+        request
+            .headers
+            .append(Header::Authorization, &format!("Digest {params}"));
+
+        request
+    }
+
+    #[test]
+    fn test_digest_params() {
+        let (_, params) =
+            digest_params(r#"username="a\"b", qop=auth,nc=00000001 , realm="""#).expect("parse");
+
+        assert_eq!(
+            params,
+            [
+                ("username".to_owned(), "a\"b".to_owned()),
+                ("qop".to_owned(), "auth".to_owned()),
+                ("nc".to_owned(), "00000001".to_owned()),
+                ("realm".to_owned(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_digest_rfc_examples() {
+        let request = |algorithm, response| {
+            digest_request(
+                "/dir/index.html",
+                &format!(
+                    "username=\"Mufasa\", realm=\"http-auth@example.org\", uri=\"/dir/index.html\", \
+                    algorithm={algorithm}, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                    nc=00000001, cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\", \
+                    qop=auth, response=\"{response}\""
+                ),
+            )
+        };
+
+        let md5 = request("MD5", "8ca523f5e9506fed4657c9700eebdbec");
+        let sha256 = request(
+            "SHA-256",
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1",
+        );
+
+        // The responses are right, but the nonce wasn't issued by this server
+        let auth = rfc_digest_auth(DigestAlgorithm::Md5, "Circle of Life");
+        assert_eq!(auth.verify(&md5), DigestOutcome::Stale);
+        assert_eq!(auth.verify(&sha256), DigestOutcome::Unauthorized);
+
+        let auth = rfc_digest_auth(DigestAlgorithm::Sha256, "Circle of Life");
+        assert_eq!(auth.verify(&sha256), DigestOutcome::Stale);
+
+        let auth = rfc_digest_auth(DigestAlgorithm::Sha256, "wrong");
+        assert_eq!(auth.verify(&sha256), DigestOutcome::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn test_digest_auth() {
+        let auth = DigestAuth::new(
+            "admin",
+            HashMap::from([("alice".to_owned(), "secret".to_owned())]),
+        );
+        let router = Router::new()
+            .get("/", |_, _| async { Response::empty(Status::Ok) })
+            .layer(auth.clone());
+
+        let response = router.handle(request(None)).await;
+        assert_eq!(response.status, Status::Unauthorized);
+
+        let challenge = &response.headers[&Header::WwwAuthenticate];
+        assert!(
+            challenge.starts_with("Digest realm=\"admin\", qop=\"auth\", algorithm=MD5, nonce=\""),
+            "{challenge}"
+        );
+        let nonce = challenge
+            .split("nonce=\"")
+            .nth(1)
+            .and_then(|nonce| nonce.split('"').next())
+            .expect("nonce");
+
+        let answer = |nonce: &str, password: &str, nc: &str| {
+            let ha1 = DigestAlgorithm::Md5.hash(&format!("alice:admin:{password}"));
+            let ha2 = DigestAlgorithm::Md5.hash("GET:/");
+            let response = DigestAlgorithm::Md5.hash(&format!("{ha1}:{nonce}:{nc}:abc:auth:{ha2}"));
+
+            digest_request(
+                "/",
+                &format!(
+                    "username=\"alice\", realm=\"admin\", nonce=\"{nonce}\", uri=\"/\", \
+                    qop=auth, nc={nc}, cnonce=\"abc\", response=\"{response}\""
+                ),
+            )
+        };
+
+        let response = router.handle(answer(nonce, "wrong", "00000001")).await;
+        assert_eq!(response.status, Status::Unauthorized);
+
+        let response = router.handle(answer(nonce, "secret", "00000001")).await;
+        assert_eq!(response.status, Status::Ok);
+
+        // The nonce is reused with the count going up, but a count can't be replayed
+        let response = router.handle(answer(nonce, "secret", "00000002")).await;
+        assert_eq!(response.status, Status::Ok);
+
+        let response = router.handle(answer(nonce, "secret", "00000002")).await;
+        assert_eq!(response.status, Status::Unauthorized);
+        assert!(response.headers[&Header::WwwAuthenticate].ends_with(", stale=true"));
+
+        // Nonces have to be signed by this server
+        let forged = format!("{nonce}0");
+        let response = router.handle(answer(&forged, "secret", "00000001")).await;
+        assert!(response.headers[&Header::WwwAuthenticate].ends_with(", stale=true"));
+
+        let expired = DigestAuth {
+            nonce_lifetime: Duration::ZERO,
+            ..auth
+        };
+        let nonce = expired.issue_nonce();
+        assert_eq!(
+            expired.verify(&answer(&nonce, "secret", "00000001")),
+            DigestOutcome::Stale
+        );
+    }
+
+    #[test]
+    fn test_answered_nonces() {
+        let mut answered = AnsweredNonces::default();
+        let not_expired = |_| false;
+
+        assert!(answered.answer("a", 10, 1, not_expired, 2));
+        assert!(answered.answer("a", 10, 3, not_expired, 2));
+        assert!(!answered.answer("a", 10, 3, not_expired, 2));
+        assert!(!answered.answer("a", 10, 2, not_expired, 2));
+
+        assert!(answered.answer("b", 20, 1, not_expired, 2));
+
+        // Full, so the oldest is forgotten, and can't be replayed as if it were new
+        assert!(answered.answer("c", 30, 1, not_expired, 2));
+        assert_eq!(answered.nonces.len(), 2);
+        assert!(!answered.answer("a", 10, 4, not_expired, 2));
+
+        // Expired nonces go first
+        assert!(answered.answer("d", 40, 1, |issued| issued < 30, 2));
+        assert!(answered.nonces.contains_key("c"));
+        assert!(answered.nonces.contains_key("d"));
+    }
+
+    fn sign(secret: &[u8], header: &Value, claims: &Value) -> String {
+        let encode = |value: &Value| URL_SAFE_NO_PAD.encode(value.to_string());
+        let message = format!("{}.{}", encode(header), encode(claims));
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac key");
+        mac.update(message.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        format!("{message}.{signature}")
+    }
+
+    #[test]
+    fn test_hmac_validator() {
+        let validator = HmacValidator::new("secret");
+        let header = serde_json::json!({ "alg": "HS256", "typ": "JWT" });
+        let claims = serde_json::json!({ "sub": "alice" });
+
+        let token = sign(b"secret", &header, &claims);
+        assert_eq!(validator.validate(&token), Ok(claims.clone()));
+
+        let token = sign(b"wrong", &header, &claims);
+        assert_eq!(validator.validate(&token), Err(AuthError::InvalidSignature));
+
+        let expired = serde_json::json!({ "sub": "alice", "exp": 1 });
+        let token = sign(b"secret", &header, &expired);
+        assert_eq!(validator.validate(&token), Err(AuthError::Expired));
+
+        let none = serde_json::json!({ "alg": "none" });
+        let token = sign(b"secret", &none, &claims);
+        assert_eq!(
+            validator.validate(&token),
+            Err(AuthError::UnsupportedAlgorithm("none".to_owned()))
+        );
+
+        assert_eq!(validator.validate("abc"), Err(AuthError::Malformed));
+    }
+
+    #[tokio::test]
+    async fn test_bearer_auth() {
+        let router = Router::new()
+            .get("/", |request: Request, _| async move {
+                let subject = request
+                    .extensions
+                    .get::<Value>()
+                    .and_then(|claims| claims["sub"].as_str())
+                    .unwrap_or_default()
+                    .to_owned();
+
+                Response::builder().body(subject).build()
+            })
+            .layer(BearerAuth::new(HmacValidator::new("secret")));
+
+        let token = sign(
+            b"secret",
+            &serde_json::json!({ "alg": "HS256" }),
+            &serde_json::json!({ "sub": "alice" }),
+        );
+
+        let response = router
+            .handle(request(Some(&format!("Bearer {token}"))))
+            .await;
+        assert_eq!(response.status, Status::Ok);
+        assert_eq!(response.body.as_bytes(), Some(b"alice".as_slice()));
+
+        let response = router.handle(request(None)).await;
+        assert_eq!(response.status, Status::Unauthorized);
+    }
+}
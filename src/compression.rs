@@ -0,0 +1,267 @@
+use crate::{
+    header::Header,
+    middleware::{Middleware, Next},
+    request::Request,
+    response::{Response, ResponseBody, Status},
+    router::BoxFuture,
+};
+use flate2::{write::GzEncoder, Compression};
+use std::io::{self, Write};
+
+/// How and which responses are gzipped
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    /// Lower is faster, [`Compression::fast`] suits latency-sensitive responses and
+    /// [`Compression::best`] ones that are rarely requested
+    pub level: Compression,
+    /// Bodies smaller than this are sent uncompressed even if the client accepts gzip
+    pub min_size: usize,
+    /// Content types worth compressing, `type/*` matches every subtype
+    ///
+    /// Images and video aren't listed by default, their formats are compressed already.
+    pub content_types: Vec<String>,
+}
+
+impl CompressionConfig {
+    /// Whether a body of `content_type`, without parameters, is worth compressing
+    pub fn compresses(&self, content_type: &str) -> bool {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+
+        self.content_types
+            .iter()
+            .any(|pattern| match pattern.strip_suffix("/*") {
+                Some(kind) => mime
+                    .split_once('/')
+                    .is_some_and(|(mime_kind, _)| mime_kind.eq_ignore_ascii_case(kind)),
+                None => mime.eq_ignore_ascii_case(pattern),
+            })
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            level: Compression::default(),
+            min_size: 128,
+            content_types: ["text/*", "application/json", "application/javascript"]
+                .map(str::to_owned)
+                .to_vec(),
+        }
+    }
+}
+
+/// Whether the `Accept-Encoding` list items include gzip with a non-zero quality
+///
+/// An explicit `gzip` item wins over `*`, which only stands for the codings not listed.
+pub fn accepts_gzip(encodings: &[String]) -> bool {
+    // The name of each item and whether it's accepted, `gzip;q=0` explicitly refuses gzip
+    let items = encodings.iter().map(|encoding| {
+        let mut parts = encoding.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+
+        let refused = parts
+            .filter_map(|param| param.strip_prefix("q="))
+            .any(|quality| quality.parse::<f32>().is_ok_and(|quality| quality == 0.0));
+
+        (name, !refused)
+    });
+
+    let mut any = None;
+    for (name, accepted) in items {
+        if name.eq_ignore_ascii_case("gzip") {
+            return accepted;
+        }
+
+        if name == "*" {
+            any = Some(accepted);
+        }
+    }
+
+    any.unwrap_or(false)
+}
+
+pub fn gzip(data: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), level);
+    encoder.write_all(data)?;
+
+    encoder.finish()
+}
+
+/// Whether `response` opted into compression and has a body of at least `config.min_size`
+/// bytes of one of `config.content_types`
+pub fn is_compressible(response: &Response, config: &CompressionConfig) -> bool {
+    let content_type = response.headers.get(&Header::ContentType);
+
+    // Without a type the body could be anything, likely already compressed
+    if !content_type.is_some_and(|content_type| config.compresses(content_type)) {
+        return false;
+    }
+
+    match &response.body {
+        ResponseBody::Sized(body) => response.compress && body.len() >= config.min_size,
+        // Chunked bodies are streamed as-is
+        ResponseBody::Chunked(_) => false,
+    }
+}
+
+/// Gzips the body of `response` at `config.level` if it [is compressible](is_compressible),
+/// weakening its ETag
+pub fn compress_response(
+    mut response: Response,
+    config: &CompressionConfig,
+) -> io::Result<Response> {
+    if !is_compressible(&response, config) {
+        return Ok(response);
+    }
+
+    let ResponseBody::Sized(body) = &response.body else {
+        return Ok(response);
+    };
+
+    let body = gzip(body, config.level)?;
+
+    response
+        .headers
+        .insert(Header::ContentEncoding, "gzip".to_owned());
+    response
+        .headers
+        .insert(Header::ContentLength, body.len().to_string());
+
+    // A strong ETag promises the exact bytes, which gzip just changed
+    if let Some(etag) = response.headers.get_mut(&Header::ETag) {
+        if !etag.starts_with("W/") {
+            etag.insert_str(0, "W/");
+        }
+    }
+
+    response.body = ResponseBody::Sized(body);
+
+    Ok(response)
+}
+
+/// Gzips responses that opted into compression when the client accepts gzip
+#[derive(Debug, Clone, Default)]
+pub struct Compress {
+    config: CompressionConfig,
+}
+
+impl Compress {
+    pub fn new(config: CompressionConfig) -> Compress {
+        Compress { config }
+    }
+}
+
+impl Middleware for Compress {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+        Box::pin(async move {
+            let accepts_gzip = accepts_gzip(request.headers.get_all(&Header::AcceptEncoding));
+
+            let mut response = next.run(request).await;
+
+            // Caches must keep the gzipped and plain variants apart, so both say what they vary on
+            if is_compressible(&response, &self.config) {
+                response.add_vary(Header::AcceptEncoding);
+            }
+
+            if !accepts_gzip {
+                return response;
+            }
+
+            match compress_response(response, &self.config) {
+                Ok(response) => response,
+                Err(err) => {
+                    tracing::error!("error compressing response: {err}");
+                    Response::empty(Status::InternalServerError)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::HeaderMap;
+
+    fn accepts(accept_encoding: &str) -> bool {
+        let mut headers = HeaderMap::new();
+        headers.append(Header::AcceptEncoding, accept_encoding);
+
+        accepts_gzip(headers.get_all(&Header::AcceptEncoding))
+    }
+
+    #[test]
+    fn test_accepts_gzip() {
+        assert!(accepts("gzip"));
+        assert!(accepts("deflate, gzip;q=0.5"));
+        assert!(accepts("*"));
+
+        assert!(!accepts("deflate, br"));
+        assert!(!accepts("gzip;q=0"));
+        assert!(!accepts("gzip;q=0, *"));
+        assert!(!accepts("*, gzip;q=0"));
+        assert!(!accepts("*;q=0"));
+        assert!(accepts("gzip, *;q=0"));
+        assert!(!accepts("invalid-encoding"));
+        assert!(!accepts(""));
+    }
+
+    #[test]
+    fn test_compresses() {
+        let config = CompressionConfig::default();
+
+        assert!(config.compresses("text/html"));
+        assert!(config.compresses("text/plain; charset=utf-8"));
+        assert!(config.compresses("Application/JSON"));
+
+        assert!(!config.compresses("image/png"));
+        assert!(!config.compresses("video/mp4"));
+        assert!(!config.compresses("application/octet-stream"));
+        assert!(!config.compresses("textual/plain"));
+    }
+
+    #[test]
+    fn test_compress_response() {
+        let response = |content_type: &str| {
+            Response::builder()
+                .header(Header::ContentType, content_type)
+                .body("hello ".repeat(100))
+                .compress()
+                .build()
+        };
+        let fast = CompressionConfig {
+            level: Compression::fast(),
+            ..CompressionConfig::default()
+        };
+
+        let compressed = compress_response(response("text/plain"), &fast).expect("compress");
+        assert_eq!(
+            compressed.headers.get(&Header::ContentEncoding),
+            Some(&"gzip".to_owned())
+        );
+        let body = compressed.body.as_bytes().expect("sized body");
+        assert_eq!(
+            body,
+            gzip(&b"hello ".repeat(100), Compression::fast()).expect("gzip")
+        );
+
+        let image = compress_response(response("image/png"), &fast).expect("compress");
+        assert!(!image.headers.contains_key(&Header::ContentEncoding));
+
+        let small = CompressionConfig {
+            min_size: 1000,
+            ..CompressionConfig::default()
+        };
+        let uncompressed = compress_response(response("text/plain"), &small).expect("compress");
+        assert!(!uncompressed.headers.contains_key(&Header::ContentEncoding));
+
+        let tagged = |etag: &str| {
+            let mut response = response("text/plain");
+            response.headers.insert(Header::ETag, etag.to_owned());
+            let response = compress_response(response, &fast).expect("compress");
+            response.headers.get(&Header::ETag).cloned()
+        };
+        assert_eq!(tagged("\"abc\""), Some("W/\"abc\"".to_owned()));
+        assert_eq!(tagged("W/\"abc\""), Some("W/\"abc\"".to_owned()));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{compression::CompressionConfig, forwarded::TrustProxy};
+use std::time::Duration;
+
+/// Tunables shared by every connection
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// How long a kept-alive connection may sit idle waiting for the next request
+    pub keep_alive_timeout: Duration,
+    /// How long a client gets to send a complete request once it has started sending it,
+    /// stalled connections are closed without a response
+    pub connection_timeout: Duration,
+    /// Larger request bodies are refused with 413 Payload Too Large
+    pub max_body_bytes: usize,
+    /// How long a client gets to send the body once the head has been read, slower uploads
+    /// get 408 Request Timeout and their connection is closed
+    pub read_body_timeout: Duration,
+    /// How long in-flight connections get to finish on shutdown before they're aborted
+    pub drain_timeout: Duration,
+    /// How and which responses are gzipped for clients that accept it
+    pub compression: CompressionConfig,
+    /// Whether `TRACE` requests are echoed back, off by default since the echo can leak
+    /// credentials such as cookies to scripts, otherwise they get 405 Method Not Allowed
+    pub allow_trace: bool,
+    /// Requests a single IP address may send in a burst before getting 429 Too Many Requests
+    pub rate_limit_capacity: u32,
+    /// Requests per second a single IP address may sustain
+    pub rate_limit_refill_rate: f64,
+    /// Connections served at once, more wait to be accepted until one closes
+    pub max_connections: usize,
+    /// Pipelined requests read ahead of the one being answered on each connection
+    pub pipeline_depth: usize,
+    /// Proxies whose `X-Forwarded-For` and `X-Real-IP` headers are believed, used for
+    /// [`Request::client_ip`](crate::request::Request::client_ip)
+    pub trust_proxy: TrustProxy,
+    /// Sent as `Via: 1.1 <server_name>` on every response when not empty, so clients can
+    /// tell there's an intermediary
+    pub server_name: String,
+    /// Whether `/robots.txt` and `/favicon.ico` are answered when the router has no routes for
+    /// them, see [`defaults::routes`](crate::defaults::routes)
+    pub serve_defaults: bool,
+    /// Sent as `Server` on every response, `None` to not tell clients what's answering them
+    pub server_header: Option<String>,
+    /// Whether a malformed header line gets 400 Bad Request, otherwise it's skipped with a
+    /// warning and counted in
+    /// [`Request::malformed_header_count`](crate::request::Request::malformed_header_count).
+    /// Header values folded over several lines get 400 too, rather than being unfolded.
+    pub strict_headers: bool,
+    /// Requests with more header lines than this are refused with 431 Request Header Fields
+    /// Too Large and their connection is closed
+    pub max_header_count: usize,
+    /// Requests with a longer request line than this are refused with 414 URI Too Long and
+    /// their connection is closed
+    pub max_request_line_bytes: usize,
+    /// Requests with a longer header line than this are refused with 431 Request Header
+    /// Fields Too Large and their connection is closed
+    pub max_header_line_bytes: usize,
+    /// Whether responses say how long reading, handling and finishing the request took in a
+    /// `Server-Timing` header, off by default since it tells clients how the server performs
+    pub server_timing: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            keep_alive_timeout: Duration::from_secs(30),
+            connection_timeout: Duration::from_secs(10),
+            max_body_bytes: 1024 * 1024,
+            read_body_timeout: Duration::from_secs(5),
+            drain_timeout: Duration::from_secs(10),
+            compression: CompressionConfig::default(),
+            allow_trace: false,
+            rate_limit_capacity: 100,
+            rate_limit_refill_rate: 20.0,
+            max_connections: 1024,
+            pipeline_depth: 4,
+            trust_proxy: TrustProxy::default(),
+            server_name: String::new(),
+            serve_defaults: true,
+            server_header: Some(format!("crsh/{}", env!("CARGO_PKG_VERSION"))),
+            strict_headers: false,
+            max_header_count: 100,
+            max_request_line_bytes: 8192,
+            max_header_line_bytes: 8192,
+            server_timing: false,
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use nom::{
+    bytes::complete::take_till,
+    character::complete::{char, space0},
+    combinator::{map, opt},
+    multi::separated_list0,
+    sequence::{pair, preceded, terminated},
+    IResult,
+};
+use std::{fmt::Write, time::Duration};
+
+/// Attributes of a cookie set with [`crate::response::ResponseBuilder::set_cookie`]
+#[derive(Debug, Clone, Default)]
+pub struct CookieOptions {
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    /// How long the cookie lives, a session cookie if `None`
+    pub max_age: Option<Duration>,
+    /// Hides the cookie from scripts
+    pub http_only: bool,
+    /// Only sends the cookie over HTTPS
+    pub secure: bool,
+}
+
+/// A cookie value without surrounding whitespace, the optional quotes aren't part of it
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Parses a `Cookie` header of `name=value` pairs separated by `; `, in the order they're sent
+///
+/// Values may be quoted as RFC 6265 allows. Pairs without a `=` or a name are skipped rather
+/// than ending the list, browsers send all sorts.
+pub fn parse_cookies(input: &str) -> IResult<&str, Vec<(&str, &str)>> {
+    let name = map(take_till(|char| char == '=' || char == ';'), str::trim);
+    let value = map(take_till(|char| char == ';'), unquote);
+    let cookie = pair(name, opt(preceded(char('='), value)));
+
+    map(
+        separated_list0(terminated(char(';'), space0), cookie),
+        |cookies| {
+            cookies
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, value?)))
+                .filter(|(name, _)| !name.is_empty())
+                .collect()
+        },
+    )(input)
+}
+
+/// The `Set-Cookie` header value for `name=value` with `options`
+pub fn set_cookie(name: &str, value: &str, options: &CookieOptions) -> String {
+    let mut cookie = format!("{name}={value}");
+
+    if let Some(path) = &options.path {
+        let _ = write!(cookie, "; Path={path}");
+    }
+    if let Some(domain) = &options.domain {
+        let _ = write!(cookie, "; Domain={domain}");
+    }
+    if let Some(max_age) = options.max_age {
+        let _ = write!(cookie, "; Max-Age={}", max_age.as_secs());
+    }
+    if options.http_only {
+        cookie.push_str("; HttpOnly");
+    }
+    if options.secure {
+        cookie.push_str("; Secure");
+    }
+
+    cookie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cookies() {
+        let (rest, cookies) =
+            parse_cookies("session=abc123; theme=\"dark\";  empty=; invalid; greeting=hello world")
+                .expect("parse cookies");
+
+        assert_eq!(rest, "");
+        assert_eq!(
+            cookies,
+            [
+                ("session", "abc123"),
+                ("theme", "dark"),
+                ("empty", ""),
+                ("greeting", "hello world"),
+            ]
+        );
+
+        let (_, cookies) = parse_cookies("a=1;b=\"two words\"; a=3").expect("parse cookies");
+        assert_eq!(cookies, [("a", "1"), ("b", "two words"), ("a", "3")]);
+
+        let (_, cookies) = parse_cookies("").expect("parse cookies");
+        assert_eq!(cookies, []);
+    }
+
+    #[test]
+    fn test_set_cookie() {
+        let options = CookieOptions {
+            path: Some("/".to_owned()),
+            max_age: Some(Duration::from_secs(3600)),
+            http_only: true,
+            secure: true,
+            ..CookieOptions::default()
+        };
+
+        assert_eq!(
+            set_cookie("session", "abc123", &options),
+            "session=abc123; Path=/; Max-Age=3600; HttpOnly; Secure"
+        );
+        assert_eq!(
+            set_cookie("theme", "dark", &CookieOptions::default()),
+            "theme=dark"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    header::Header,
+    middleware::{Middleware, Next},
+    request::{Method, Request},
+    response::{Response, Status},
+    router::BoxFuture,
+};
+use itertools::Itertools;
+
+/// Which cross-origin requests browsers should be allowed to make
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// Origins allowed to make requests, `*` allows any origin
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<String>,
+}
+
+impl CorsConfig {
+    /// Whether `request` is a preflight check rather than an actual request
+    pub fn is_preflight(request: &Request) -> bool {
+        request.method == Method::OPTIONS
+            && request.headers.contains(&Header::Origin)
+            && request
+                .headers
+                .contains(&Header::AccessControlRequestMethod)
+    }
+
+    /// The value of `Access-Control-Allow-Origin` for `origin`, if it's allowed
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        self.allowed_origins.iter().find_map(|allowed| {
+            if allowed == "*" {
+                Some("*".to_owned())
+            } else if allowed == origin {
+                Some(origin.to_owned())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Answers a preflight request without invoking any route
+    pub fn preflight(&self, origin: Option<&str>) -> Response {
+        let allow_origin = origin.and_then(|origin| self.allow_origin(origin));
+        let mut response = Response::builder().status(Status::NoContent);
+
+        if let Some(allow_origin) = &allow_origin {
+            response = response
+                .header(Header::AccessControlAllowOrigin, allow_origin)
+                .header(
+                    Header::AccessControlAllowMethods,
+                    self.allowed_methods.iter().join(", "),
+                )
+                .header(
+                    Header::AccessControlAllowHeaders,
+                    self.allowed_headers.iter().join(", "),
+                );
+        }
+
+        // Without the allow headers the browser rejects the actual request
+        let mut response = response.build();
+        vary_on_origin(allow_origin.as_deref(), &mut response);
+
+        response
+    }
+
+    /// Adds the CORS headers for `origin` to a regular response
+    pub fn apply(&self, origin: Option<&str>, response: &mut Response) {
+        let allow_origin = origin.and_then(|origin| self.allow_origin(origin));
+        vary_on_origin(allow_origin.as_deref(), response);
+
+        if let Some(allow_origin) = allow_origin {
+            response
+                .headers
+                .insert(Header::AccessControlAllowOrigin, allow_origin);
+        }
+    }
+}
+
+/// Unless every origin gets `*`, the response depends on the origin, so caches must keep
+/// them apart
+fn vary_on_origin(allow_origin: Option<&str>, response: &mut Response) {
+    if allow_origin != Some("*") {
+        response.add_vary(Header::Origin);
+    }
+}
+
+/// Answers preflight requests without invoking the route and adds CORS headers to the rest
+impl Middleware for CorsConfig {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+        Box::pin(async move {
+            let origin = request
+                .headers
+                .get_first(&Header::Origin)
+                .map(str::to_owned);
+
+            if CorsConfig::is_preflight(&request) {
+                return self.preflight(origin.as_deref());
+            }
+
+            let mut response = next.run(request).await;
+            self.apply(origin.as_deref(), &mut response);
+
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: vec!["https://example.com".to_owned()],
+            allowed_methods: vec![Method::GET, Method::POST],
+            allowed_headers: vec!["Content-Type".to_owned()],
+        }
+    }
+
+    #[test]
+    fn test_preflight() {
+        let response = config().preflight(Some("https://example.com"));
+
+        assert_eq!(response.status, Status::NoContent);
+        assert_eq!(
+            response.headers.get(&Header::AccessControlAllowOrigin),
+            Some(&"https://example.com".to_owned())
+        );
+        assert_eq!(
+            response.headers.get(&Header::AccessControlAllowMethods),
+            Some(&"GET, POST".to_owned())
+        );
+        assert_eq!(
+            response.headers.get(&Header::Vary),
+            Some(&"Origin".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_preflight_disallowed_origin() {
+        let response = config().preflight(Some("https://evil.com"));
+
+        assert_eq!(response.status, Status::NoContent);
+        assert!(!response
+            .headers
+            .contains_key(&Header::AccessControlAllowOrigin));
+        assert_eq!(
+            response.headers.get(&Header::Vary),
+            Some(&"Origin".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut response = Response::empty(Status::Ok);
+        config().apply(Some("https://example.com"), &mut response);
+
+        assert_eq!(
+            response.headers.get(&Header::AccessControlAllowOrigin),
+            Some(&"https://example.com".to_owned())
+        );
+        assert_eq!(
+            response.headers.get(&Header::Vary),
+            Some(&"Origin".to_owned())
+        );
+
+        let any = CorsConfig {
+            allowed_origins: vec!["*".to_owned()],
+            ..config()
+        };
+        let mut response = Response::empty(Status::Ok);
+        any.apply(Some("https://example.com"), &mut response);
+
+        assert_eq!(
+            response.headers.get(&Header::AccessControlAllowOrigin),
+            Some(&"*".to_owned())
+        );
+        assert!(!response.headers.contains_key(&Header::Vary));
+    }
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::time::SystemTime;
+
+/// The preferred format from RFC 7231, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+const IMF_FIXDATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Obsolete formats recipients must still accept, e.g. `Sunday, 06-Nov-94 08:49:37 GMT`
+/// (RFC 850) and `Sun Nov  6 08:49:37 1994` (asctime)
+const OBSOLETE_FORMATS: [&str; 2] = ["%A, %d-%b-%y %H:%M:%S GMT", "%a %b %e %H:%M:%S %Y"];
+
+/// Formats `time` as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn format_http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format(IMF_FIXDATE).to_string()
+}
+
+/// Parses an HTTP date in any of the formats allowed by RFC 7231, `None` if it's invalid
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let date = date.trim();
+
+    [IMF_FIXDATE]
+        .iter()
+        .chain(&OBSOLETE_FORMATS)
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .map(|date| date.and_utc().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_http_date() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+
+        assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(time)
+        );
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(time));
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{header::Header, response::Response, router::Router};
+use std::time::Duration;
+
+/// Asks crawlers to stay away, nothing served here is meant to be indexed
+const ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\n";
+
+/// A 1×1 transparent PNG, which browsers accept as an icon
+const FAVICON: &[u8] = &[
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1F, 0x15, 0xC4,
+    0x89, 0x00, 0x00, 0x00, 0x0B, 0x49, 0x44, 0x41, 0x54, 0x78, 0xDA, 0x63, 0x60, 0x00, 0x02, 0x00,
+    0x00, 0x05, 0x00, 0x01, 0xE9, 0xFA, 0xDC, 0xD8, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44,
+    0xAE, 0x42, 0x60, 0x82,
+];
+
+/// Lets browsers and crawlers keep them for a day instead of asking on every visit
+const CACHE_FOR: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Registers `GET /robots.txt` and `GET /favicon.ico`, which browsers and crawlers request
+/// whether or not the server has them
+///
+/// Added after any routes already on `router`, so those take precedence.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/robots.txt", |_, _| async {
+            Response::builder()
+                .header(Header::ContentType, "text/plain")
+                .cache_for(CACHE_FOR)
+                .body(ROBOTS_TXT)
+                .build()
+        })
+        .get("/favicon.ico", |_, _| async {
+            Response::builder()
+                .header(Header::ContentType, "image/png")
+                .cache_for(CACHE_FOR)
+                .body(FAVICON)
+                .build()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        request::{Method, Request},
+        response::Status,
+    };
+
+    #[tokio::test]
+    async fn test_defaults() {
+        let router = routes(Router::new());
+
+        let response = router
+            .handle(Request::new(Method::GET, "/robots.txt"))
+            .await;
+        assert_eq!(response.body.as_bytes(), Some(ROBOTS_TXT.as_bytes()));
+
+        let response = router
+            .handle(Request::new(Method::GET, "/favicon.ico"))
+            .await;
+        assert_eq!(response.status, Status::Ok);
+        assert!(response
+            .body
+            .as_bytes()
+            .is_some_and(|body| body.starts_with(b"\x89PNG\r\n\x1a\n")));
+    }
+
+    #[tokio::test]
+    async fn test_own_routes_take_precedence() {
+        let router = Router::new().get("/robots.txt", |_, _| async {
+            Response::builder().body("User-agent: *\n").build()
+        });
+        let router = routes(router);
+
+        let response = router
+            .handle(Request::new(Method::GET, "/robots.txt"))
+            .await;
+        assert_eq!(
+            response.body.as_bytes(),
+            Some(b"User-agent: *\n".as_slice())
+        );
+    }
+}
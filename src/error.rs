@@ -0,0 +1,106 @@
+use crate::{
+    header::Header,
+    response::{IntoResponse, Response, Status},
+};
+use thiserror::Error;
+
+/// An error a handler can return with `?`, answered with the matching status
+///
+/// Client errors with a message send it as a plain text body. Internal errors are logged but
+/// never sent, since they may contain details the client shouldn't see.
+#[derive(Debug, Error)]
+pub enum HttpError {
+    #[error("bad request: {0}")]
+    BadRequest(String),
+
+    #[error("unauthorized")]
+    Unauthorized,
+
+    #[error("forbidden")]
+    Forbidden,
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("method not allowed")]
+    MethodNotAllowed,
+
+    #[error("conflict: {0}")]
+    Conflict(String),
+
+    #[error("payload too large")]
+    PayloadTooLarge,
+
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    #[error(transparent)]
+    InternalServerError(#[from] anyhow::Error),
+}
+
+impl HttpError {
+    pub fn status(&self) -> Status {
+        match self {
+            HttpError::BadRequest(_) => Status::BadRequest,
+            HttpError::Unauthorized => Status::Unauthorized,
+            HttpError::Forbidden => Status::Forbidden,
+            HttpError::NotFound => Status::NotFound,
+            HttpError::MethodNotAllowed => Status::MethodNotAllowed,
+            HttpError::Conflict(_) => Status::Conflict,
+            HttpError::PayloadTooLarge => Status::PayloadTooLarge,
+            HttpError::UnsupportedMediaType(_) => Status::UnsupportedMediaType,
+            HttpError::InternalServerError(_) => Status::InternalServerError,
+        }
+    }
+}
+
+impl From<HttpError> for Response {
+    fn from(err: HttpError) -> Response {
+        let status = err.status();
+
+        match err {
+            HttpError::BadRequest(message)
+            | HttpError::Conflict(message)
+            | HttpError::UnsupportedMediaType(message) => Response::builder()
+                .status(status)
+                .header(Header::ContentType, "text/plain")
+                .body(message)
+                .build(),
+            HttpError::InternalServerError(err) => {
+                tracing::error!("internal server error: {err:#}");
+                Response::empty(status)
+            }
+            _ => Response::empty(status),
+        }
+    }
+}
+
+impl<E: Into<HttpError>> IntoResponse for Result<Response, E> {
+    fn into_response(self) -> Response {
+        self.unwrap_or_else(|err| err.into().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_into_response() {
+        let response = Response::from(HttpError::BadRequest("missing name".to_owned()));
+        assert_eq!(response.status, Status::BadRequest);
+        assert_eq!(response.body.as_bytes(), Some(b"missing name".as_slice()));
+
+        let response = Response::from(HttpError::NotFound);
+        assert_eq!(response.status, Status::NotFound);
+
+        // The cause stays in the logs
+        let response = Response::from(HttpError::from(anyhow!("disk on fire")));
+        assert_eq!(response.status, Status::InternalServerError);
+        assert_eq!(response.body.as_bytes(), Some(b"".as_slice()));
+
+        let result: Result<Response, HttpError> = Ok(Response::empty(Status::Created));
+        assert_eq!(result.into_response().status, Status::Created);
+    }
+}
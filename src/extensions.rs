@@ -0,0 +1,57 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt::{self, Debug, Formatter},
+};
+
+/// Values attached to a request by middleware for handlers further down, one per type
+#[derive(Default)]
+pub struct Extensions(HashMap<TypeId, Box<dyn Any + Send + Sync>>);
+
+impl Extensions {
+    pub fn new() -> Extensions {
+        Extensions::default()
+    }
+
+    /// Stores `value`, returning the previous value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.0
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.0.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+}
+
+impl Debug for Extensions {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct User(&'static str);
+
+    #[test]
+    fn test_extensions() {
+        let mut extensions = Extensions::new();
+        assert_eq!(extensions.get::<User>(), None);
+
+        assert_eq!(extensions.insert(User("alice")), None);
+        assert_eq!(extensions.insert(42_u32), None);
+        assert_eq!(extensions.insert(User("bob")), Some(User("alice")));
+
+        assert_eq!(extensions.get::<User>(), Some(&User("bob")));
+        assert_eq!(extensions.get::<u32>(), Some(&42));
+        assert_eq!(extensions.get::<u64>(), None);
+    }
+}
@@ -0,0 +1,809 @@
+use crate::{
+    date::{format_http_date, parse_http_date},
+    error::HttpError,
+    header::Header,
+    mime,
+    percent::percent_encode,
+    request::Request,
+    response::{Response, Status, STREAM_CHUNK_SIZE},
+    router::{Params, Router},
+};
+use anyhow::Context;
+use std::{
+    io,
+    io::SeekFrom,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+/// Where files are served from and how directories are answered
+#[derive(Debug, Clone)]
+pub struct StaticConfig {
+    pub root: PathBuf,
+    /// Whether a directory without an index file lists its files, rather than being
+    /// forbidden
+    pub directory_listing: bool,
+    /// The file served for a directory instead of a listing, if it has one
+    pub index_file: Option<String>,
+    /// Whether directory listings include dotfiles
+    pub show_hidden: bool,
+}
+
+impl StaticConfig {
+    /// Serves `index.html` for directories that have one and lists the others
+    pub fn new(root: PathBuf) -> StaticConfig {
+        StaticConfig {
+            root,
+            directory_listing: true,
+            index_file: Some("index.html".to_owned()),
+            show_hidden: false,
+        }
+    }
+}
+
+/// Registers `GET`, `POST` and `DELETE` routes for `/files/:filename` serving files from the
+/// root, and `POST /upload` storing the files of a `multipart/form-data` form in it
+///
+/// Files in the root's subdirectories are served at `/files/:dir/:filename`. `GET /files/`
+/// and `GET /files/:dir/` answer with the directory's index file, or list the files in it if
+/// there is none. Adding `?download` to a file's URL makes browsers download it rather than
+/// display it.
+pub fn routes(router: Router, config: StaticConfig) -> Router {
+    let config = Arc::new(config);
+    let index_config = config.clone();
+    let dir_config = config.clone();
+    let get_config = config.clone();
+    let nested_config = config.clone();
+    let delete_config = config.clone();
+    let upload_config = config.clone();
+
+    router
+        .get("/files/", move |request, _| {
+            let config = index_config.clone();
+
+            async move { get_directory(&config.root, "/files/", request, &config).await }
+        })
+        .get("/files/:dir/", move |request, params| {
+            let config = dir_config.clone();
+
+            async move {
+                let dir = resolve(&config.root, &params)?;
+                let url_path = format!(
+                    "/files/{}/",
+                    percent_encode(params.get("dir").unwrap_or_default())
+                );
+
+                get_directory(&dir, &url_path, request, &config).await
+            }
+        })
+        .get("/files/:filename", move |request, params| {
+            let config = get_config.clone();
+
+            async move { get_file(&config.root, request, &params).await }
+        })
+        .get("/files/:dir/:filename", move |request, params| {
+            let config = nested_config.clone();
+
+            async move { get_file(&config.root, request, &params).await }
+        })
+        .post("/files/:filename", move |request, params| {
+            let config = config.clone();
+
+            async move { post_file(&config.root, request, &params).await }
+        })
+        .delete("/files/:filename", move |_, params| {
+            let config = delete_config.clone();
+
+            async move { delete_file(&config.root, &params).await }
+        })
+        .post("/upload", move |request, _| {
+            let config = upload_config.clone();
+
+            async move { upload(&config.root, request).await }
+        })
+}
+
+/// Resolves the `dir` and `filename` params inside `root`, rejecting path traversal
+fn resolve(root: &Path, params: &Params) -> Result<PathBuf, HttpError> {
+    let mut path = root.to_owned();
+
+    for name in ["dir", "filename"] {
+        if let Some(name) = params.get(name) {
+            path = resolve_filename(&path, name)?;
+        }
+    }
+
+    Ok(path)
+}
+
+fn resolve_filename(root: &Path, filename: &str) -> Result<PathBuf, HttpError> {
+    // These name a directory rather than a file in it, e.g. in an upload's `filename=""`
+    if matches!(filename, "" | "." | "..") {
+        return Err(HttpError::BadRequest(format!(
+            "invalid filename {filename:?}"
+        )));
+    }
+
+    // Separators can sneak in through percent-encoding, e.g. `%2Fetc%2Fpasswd`
+    if filename.contains("..") || filename.contains(['/', '\\']) {
+        return Err(HttpError::Forbidden);
+    }
+
+    Ok(root.join(filename))
+}
+
+/// A weak ETag for `content`, weak since compression may change the bytes sent
+fn etag(content: &[u8]) -> String {
+    format!("W/\"{:08x}\"", crc32fast::hash(content))
+}
+
+/// A weak ETag for a file too large to hash, from its length and modification time
+fn metadata_etag(len: u64, modified: Option<SystemTime>) -> String {
+    let modified = modified
+        .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    format!("W/\"{len:x}-{:x}\"", modified.as_nanos())
+}
+
+/// Whether `If-None-Match` lists `etag`, using the weak comparison from RFC 7232
+fn is_not_modified(request: &Request, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim_start_matches("W/").to_owned();
+
+    request
+        .headers
+        .get_all(&Header::IfNoneMatch)
+        .iter()
+        .any(|tag| tag == "*" || opaque(tag) == opaque(etag))
+}
+
+/// Whether the `If-Modified-Since` header of `request` is no earlier than `modified`
+///
+/// Ignored when `If-None-Match` is present, as the ETag is the more precise validator.
+fn is_unmodified_since(request: &Request, modified: SystemTime) -> bool {
+    if request.headers.contains(&Header::IfNoneMatch) {
+        return false;
+    }
+
+    let Some(since) = request
+        .headers
+        .get_first(&Header::IfModifiedSince)
+        .and_then(parse_http_date)
+    else {
+        return false;
+    };
+
+    // HTTP dates only have second precision
+    let modified = modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| Duration::from_secs(elapsed.as_secs()));
+
+    modified.is_ok_and(|modified| SystemTime::UNIX_EPOCH + modified <= since)
+}
+
+/// What a `Range` header asks for in a file of a given length
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+    /// The bytes to send, with `end` exclusive
+    Satisfiable(Range<u64>),
+    /// The range is entirely outside the file
+    Unsatisfiable,
+    /// Not a single byte range, the whole file is sent instead
+    Ignored,
+}
+
+/// Parses `bytes=start-end`, `bytes=start-` or `bytes=-suffix` for a file of `len` bytes
+fn parse_range(range: &str, len: u64) -> RangeRequest {
+    let Some((start, end)) = range
+        .strip_prefix("bytes=")
+        // Multiple ranges aren't supported
+        .filter(|range| !range.contains(','))
+        .and_then(|range| range.trim().split_once('-'))
+    else {
+        return RangeRequest::Ignored;
+    };
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => start..end.saturating_add(1).min(len),
+        (Ok(start), Err(_)) if end.is_empty() => start..len,
+        (Err(_), Ok(suffix)) if start.is_empty() => len.saturating_sub(suffix)..len,
+        _ => return RangeRequest::Ignored,
+    };
+
+    if range.is_empty() {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    RangeRequest::Satisfiable(range)
+}
+
+/// Escapes `text` for use in HTML text and quoted attribute values
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            char => escaped.push(char),
+        }
+    }
+
+    escaped
+}
+
+/// An HTML page linking to the files in `dir`, which is served at `url_path`, sorted by name
+///
+/// Only the root's own subdirectories are served, so those are the only ones linked, deeper
+/// ones are listed with a trailing `/`. Names that aren't utf-8 can't be requested, so
+/// they're left out.
+async fn list_directory(dir: &Path, url_path: &str, config: &StaticConfig) -> io::Result<String> {
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(dir).await?;
+
+    while let Some(entry) = read_dir.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+
+        if name.starts_with('.') && !config.show_hidden {
+            continue;
+        }
+
+        entries.push((name, entry.file_type().await?.is_dir()));
+    }
+
+    entries.sort();
+
+    let is_root = url_path == "/files/";
+
+    let mut items = String::new();
+    for (name, is_dir) in entries {
+        let item = match (is_dir, is_root) {
+            (true, true) => format!(
+                "<li><a href=\"{url_path}{}/\">{}/</a></li>\n",
+                percent_encode(&name),
+                html_escape(&name)
+            ),
+            (true, false) => format!("<li>{}/</li>\n", html_escape(&name)),
+            (false, _) => format!(
+                "<li><a href=\"{url_path}{}\">{}</a></li>\n",
+                percent_encode(&name),
+                html_escape(&name)
+            ),
+        };
+
+        items.push_str(&item);
+    }
+
+    let title = html_escape(url_path);
+
+    Ok(format!(
+        "<!DOCTYPE html>\n\
+        <html>\n\
+        <head><meta charset=\"utf-8\"><title>Directory listing for {title}</title></head>\n\
+        <body>\n\
+        <h1>Directory listing for {title}</h1>\n\
+        <hr>\n\
+        <ul>\n{items}</ul>\n\
+        <hr>\n\
+        </body>\n\
+        </html>\n"
+    ))
+}
+
+/// Answers a request for the directory `dir`, which is served at `url_path`
+///
+/// Like nginx's `try_files $uri/index.html $uri/`, the index file is served if there is one,
+/// then a listing if those are enabled, and the directory is forbidden otherwise.
+async fn get_directory(
+    dir: &Path,
+    url_path: &str,
+    request: Request,
+    config: &StaticConfig,
+) -> Result<Response, HttpError> {
+    match tokio::fs::metadata(dir).await {
+        Ok(metadata) if metadata.is_dir() => {}
+        Ok(_) => return Err(HttpError::NotFound),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(HttpError::NotFound),
+        Err(err) => {
+            return Err(anyhow::Error::new(err)
+                .context(format!("read {}", dir.display()))
+                .into())
+        }
+    }
+
+    if let Some(index_file) = &config.index_file {
+        let index = dir.join(index_file);
+
+        match serve_file(&index, &request).await {
+            Ok(response) => return Ok(response),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(anyhow::Error::new(err)
+                    .context(format!("read {}", index.display()))
+                    .into())
+            }
+        }
+    }
+
+    if !config.directory_listing {
+        return Err(HttpError::Forbidden);
+    }
+
+    let listing = list_directory(dir, url_path, config)
+        .await
+        .with_context(|| format!("list {}", dir.display()))?;
+
+    Ok(Response::builder()
+        .header(Header::ContentType, "text/html; charset=utf-8")
+        .body(listing)
+        .compress()
+        .build())
+}
+
+async fn get_file(root: &Path, request: Request, params: &Params) -> Result<Response, HttpError> {
+    let path = resolve(root, params)?;
+
+    match serve_file(&path, &request).await {
+        Ok(response) => Ok(response),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Err(HttpError::NotFound),
+        Err(err) => Err(anyhow::Error::new(err)
+            .context(format!("read {}", path.display()))
+            .into()),
+    }
+}
+
+/// Responds with the file at `path`, or the part of it asked for by a `Range` header
+async fn serve_file(path: &Path, request: &Request) -> io::Result<Response> {
+    let mut file = File::open(path).await?;
+    let metadata = file.metadata().await?;
+    let len = metadata.len();
+
+    // Directories are answered by `get_directory`
+    if metadata.is_dir() {
+        return Err(io::ErrorKind::NotFound.into());
+    }
+
+    let mut response = Response::builder()
+        .header(Header::ContentType, mime::mime_for_path(path))
+        .header(Header::AcceptRanges, "bytes");
+
+    // `?download` saves the file instead of opening it in the browser
+    if request.query.contains_key("download") {
+        if let Some(filename) = path.file_name().and_then(|name| name.to_str()) {
+            response = response.attachment(filename);
+        }
+    }
+
+    if let Ok(modified) = metadata.modified() {
+        let last_modified = format_http_date(modified);
+
+        if is_unmodified_since(request, modified) {
+            return Ok(Response::builder()
+                .status(Status::NotModified)
+                .header(Header::LastModified, last_modified)
+                .build());
+        }
+
+        response = response.header(Header::LastModified, last_modified);
+    }
+
+    // Files larger than a chunk are streamed rather than read into memory, so they can't be
+    // hashed or compressed
+    let body = if len > STREAM_CHUNK_SIZE as u64 {
+        None
+    } else {
+        let mut body = Vec::new();
+        file.read_to_end(&mut body).await?;
+        Some(body)
+    };
+
+    let etag = match &body {
+        Some(body) => etag(body),
+        None => metadata_etag(len, metadata.modified().ok()),
+    };
+
+    // A cached copy is answered before any range, the client already has all of it
+    if is_not_modified(request, &etag) {
+        return Ok(not_modified(etag));
+    }
+
+    let response = response.header(Header::ETag, etag);
+
+    let range = request
+        .headers
+        .get_first(&Header::Range)
+        .map_or(RangeRequest::Ignored, |range| parse_range(range, len));
+
+    match range {
+        RangeRequest::Satisfiable(range) => {
+            let range_len = range.end - range.start;
+
+            let response = response.status(Status::PartialContent).header(
+                Header::ContentRange,
+                format!("bytes {}-{}/{len}", range.start, range.end - 1),
+            );
+
+            if let Some(body) = body {
+                let body = body[range.start as usize..range.end as usize].to_vec();
+                return Ok(response.body(body).build());
+            }
+
+            file.seek(SeekFrom::Start(range.start)).await?;
+
+            Ok(if range_len > STREAM_CHUNK_SIZE as u64 {
+                response.chunked_body(file.take(range_len)).build()
+            } else {
+                let mut body = vec![0; range_len as usize];
+                file.read_exact(&mut body).await?;
+
+                response.body(body).build()
+            })
+        }
+        RangeRequest::Unsatisfiable => Ok(Response::builder()
+            .status(Status::RangeNotSatisfiable)
+            .header(Header::ContentRange, format!("bytes */{len}"))
+            .build()),
+        RangeRequest::Ignored => Ok(match body {
+            Some(body) => response.body(body).compress().build(),
+            None => response.chunked_body(file).build(),
+        }),
+    }
+}
+
+fn not_modified(etag: String) -> Response {
+    Response::builder()
+        .status(Status::NotModified)
+        .header(Header::ETag, etag)
+        .build()
+}
+
+async fn post_file(root: &Path, request: Request, params: &Params) -> Result<Response, HttpError> {
+    let path = resolve(root, params)?;
+
+    tokio::fs::write(&path, &request.body)
+        .await
+        .with_context(|| format!("write {}", path.display()))?;
+
+    Ok(Response::empty(Status::Created))
+}
+
+async fn delete_file(root: &Path, params: &Params) -> Result<Response, HttpError> {
+    let path = resolve(root, params)?;
+
+    let metadata = match tokio::fs::metadata(&path).await {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(HttpError::NotFound),
+        Err(err) => {
+            return Err(anyhow::Error::new(err)
+                .context(format!("read {}", path.display()))
+                .into())
+        }
+    };
+
+    // Only files are served, so only files can be deleted
+    if metadata.is_dir() {
+        return Err(HttpError::Forbidden);
+    }
+
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => Ok(Response::empty(Status::NoContent)),
+        // Deleted by another request in the meantime
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Err(HttpError::NotFound),
+        Err(err) => Err(anyhow::Error::new(err)
+            .context(format!("delete {}", path.display()))
+            .into()),
+    }
+}
+
+/// Stores every file field of the form under its client-side filename
+async fn upload(root: &Path, request: Request) -> Result<Response, HttpError> {
+    let parts = request
+        .multipart()
+        .map_err(|err| HttpError::BadRequest(format!("invalid upload: {err:#}")))?;
+
+    let files: Vec<_> = parts
+        .iter()
+        .filter_map(|part| Some((part.filename.as_deref()?, &part.data)))
+        .collect();
+
+    if files.is_empty() {
+        return Err(HttpError::BadRequest("no files in the upload".to_owned()));
+    }
+
+    // Every name is checked first, so an invalid one doesn't leave the others half written
+    let files = files
+        .into_iter()
+        .map(|(filename, data)| Ok((resolve_filename(root, filename)?, data)))
+        .collect::<Result<Vec<_>, HttpError>>()?;
+
+    for (path, data) in files {
+        tokio::fs::write(&path, data)
+            .await
+            .with_context(|| format!("write {}", path.display()))?;
+    }
+
+    Ok(Response::empty(Status::Created))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Method;
+
+    #[test]
+    fn test_is_not_modified() {
+        let etag = etag(b"hello");
+
+        let mut request = Request::new(Method::GET, "/files/hello.txt");
+        assert!(!is_not_modified(&request, &etag));
+
+        request
+            .headers
+            .append(Header::IfNoneMatch, "\"other\", W/\"3610a686\"");
+        assert!(is_not_modified(&request, &etag));
+
+        let mut request = Request::new(Method::GET, "/files/hello.txt");
+        request.headers.append(Header::IfNoneMatch, "\"other\"");
+        assert!(!is_not_modified(&request, &etag));
+    }
+
+    #[test]
+    fn test_is_unmodified_since() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_millis(784_111_777_500);
+
+        let mut request = Request::new(Method::GET, "/files/hello.txt");
+        assert!(!is_unmodified_since(&request, modified));
+
+        request
+            .headers
+            .append(Header::IfModifiedSince, "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert!(is_unmodified_since(&request, modified));
+        assert!(!is_unmodified_since(
+            &request,
+            modified + Duration::from_secs(1)
+        ));
+
+        request.headers.append(Header::IfNoneMatch, "\"other\"");
+        assert!(!is_unmodified_since(&request, modified));
+    }
+
+    #[tokio::test]
+    async fn test_list_directory() {
+        let temp = tempfile::tempdir().expect("create dir");
+        let dir = temp.path();
+        tokio::fs::create_dir_all(dir.join("sub"))
+            .await
+            .expect("create dir");
+        for name in ["b <i>.txt", "a.txt", ".hidden"] {
+            tokio::fs::write(dir.join(name), "")
+                .await
+                .expect("write file");
+        }
+
+        let mut config = StaticConfig::new(dir.to_owned());
+        let listing = list_directory(dir, "/files/", &config).await;
+        let nested = list_directory(dir, "/files/sub/", &config).await;
+        config.show_hidden = true;
+        let hidden = list_directory(dir, "/files/", &config).await;
+
+        let listing = listing.expect("list directory");
+        let a = listing
+            .find("<li><a href=\"/files/a.txt\">a.txt</a></li>")
+            .expect("a.txt listed");
+        let b = listing
+            .find("<li><a href=\"/files/b%20%3Ci%3E.txt\">b &lt;i&gt;.txt</a></li>")
+            .expect("b <i>.txt listed");
+        assert!(a < b);
+        assert!(listing.contains("<li><a href=\"/files/sub/\">sub/</a></li>"));
+        assert!(!listing.contains(".hidden"));
+
+        let nested = nested.expect("list directory");
+        assert!(nested.contains("<li><a href=\"/files/sub/a.txt\">a.txt</a></li>"));
+        assert!(nested.contains("<li>sub/</li>"));
+
+        assert!(hidden.expect("list directory").contains(".hidden"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_file() {
+        let temp = tempfile::tempdir().expect("create dir");
+        let dir = temp.path();
+        tokio::fs::create_dir_all(dir.join("sub"))
+            .await
+            .expect("create dir");
+        tokio::fs::write(dir.join("a.txt"), "a")
+            .await
+            .expect("write file");
+
+        let router = routes(Router::new(), StaticConfig::new(dir.to_owned()));
+        let delete = |path| router.handle(Request::new(Method::DELETE, path));
+
+        let deleted = delete("/files/a.txt").await;
+        let exists = tokio::fs::try_exists(dir.join("a.txt")).await;
+        let missing = delete("/files/a.txt").await;
+        let directory = delete("/files/sub").await;
+
+        assert_eq!(deleted.status, Status::NoContent);
+        assert!(deleted.body.as_bytes().is_some_and(<[u8]>::is_empty));
+        assert!(!exists.expect("check file"));
+        assert_eq!(missing.status, Status::NotFound);
+        assert_eq!(directory.status, Status::Forbidden);
+    }
+
+    #[tokio::test]
+    async fn test_upload_invalid_filename() {
+        let temp = tempfile::tempdir().expect("create dir");
+        let dir = temp.path();
+
+        let router = routes(Router::new(), StaticConfig::new(dir.to_owned()));
+        let upload = |filenames: &[&str]| {
+            let body: String = filenames
+                .iter()
+                .map(|filename| {
+                    format!(
+                        "--x\r\nContent-Disposition: form-data; name=\"file\"; \
+                         filename=\"{filename}\"\r\n\r\ndata\r\n"
+                    )
+                })
+                .chain(["--x--\r\n".to_owned()])
+                .collect();
+
+            let mut request = Request::new(Method::POST, "/upload");
+            request
+                .headers
+                .append(Header::ContentType, "multipart/form-data; boundary=x");
+            request.body = body.into_bytes();
+            router.handle(request)
+        };
+
+        let empty = upload(&["a.txt", ""]).await;
+        let dot = upload(&["."]).await;
+        let dot_dot = upload(&[".."]).await;
+        let written = tokio::fs::try_exists(dir.join("a.txt")).await;
+        let valid = upload(&["a.txt", "b.txt"]).await;
+        let both = tokio::fs::read_to_string(dir.join("b.txt")).await;
+
+        assert_eq!(empty.status, Status::BadRequest);
+        assert_eq!(dot.status, Status::BadRequest);
+        assert_eq!(dot_dot.status, Status::BadRequest);
+        assert!(!written.expect("check file"));
+
+        assert_eq!(valid.status, Status::Created);
+        assert_eq!(both.expect("read file"), "data");
+    }
+
+    #[tokio::test]
+    async fn test_get_file_range() {
+        let temp = tempfile::tempdir().expect("create dir");
+        let dir = temp.path();
+        tokio::fs::write(dir.join("a.txt"), "hello world")
+            .await
+            .expect("write file");
+
+        let router = routes(Router::new(), StaticConfig::new(dir.to_owned()));
+        let get = |range: String| {
+            let mut request = Request::new(Method::GET, "/files/a.txt");
+            request.headers.append(Header::Range, &range);
+            router.handle(request)
+        };
+
+        let partial = get("bytes=6-".to_owned()).await;
+        let everything = get(format!("bytes=0-{}", u64::MAX)).await;
+
+        let etag = partial.headers.get(&Header::ETag).expect("etag").clone();
+        let mut request = Request::new(Method::GET, "/files/a.txt");
+        request.headers.append(Header::Range, "bytes=6-");
+        request.headers.append(Header::IfNoneMatch, &etag);
+        let cached = router.handle(request).await;
+
+        assert_eq!(partial.status, Status::PartialContent);
+        assert_eq!(partial.body.as_bytes(), Some(&b"world"[..]));
+
+        assert_eq!(cached.status, Status::NotModified);
+        assert_eq!(cached.headers.get(&Header::ETag), Some(&etag));
+
+        assert_eq!(everything.status, Status::PartialContent);
+        assert_eq!(everything.body.as_bytes(), Some(&b"hello world"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_get_directory() {
+        let temp = tempfile::tempdir().expect("create dir");
+        let dir = temp.path();
+        tokio::fs::create_dir_all(dir.join("site"))
+            .await
+            .expect("create dir");
+        tokio::fs::create_dir_all(dir.join("empty"))
+            .await
+            .expect("create dir");
+        tokio::fs::write(dir.join("site/index.html"), "<h1>hello</h1>")
+            .await
+            .expect("write file");
+
+        let mut config = StaticConfig::new(dir.to_owned());
+        let get = |path: &str, config: &StaticConfig| {
+            let dir = dir.join(path);
+            let config = config.clone();
+
+            async move {
+                let request = Request::new(Method::GET, "/files/");
+                get_directory(&dir, "/files/", request, &config).await
+            }
+        };
+
+        let index = get("site", &config).await;
+        let listing = get("empty", &config).await;
+        let missing = get("missing", &config).await;
+
+        config.directory_listing = false;
+        let forbidden = get("empty", &config).await;
+        let unlisted_index = get("site", &config).await;
+
+        config.index_file = None;
+        let no_index = get("site", &config).await;
+
+        let index = index.expect("index");
+        assert_eq!(index.body.as_bytes(), Some(&b"<h1>hello</h1>"[..]));
+        assert_eq!(
+            index.headers.get(&Header::ContentType).map(String::as_str),
+            Some("text/html")
+        );
+
+        let listing = listing.expect("listing");
+        assert!(
+            String::from_utf8_lossy(listing.body.as_bytes().expect("body"))
+                .contains("Directory listing")
+        );
+
+        assert!(matches!(missing, Err(HttpError::NotFound)));
+        assert!(matches!(forbidden, Err(HttpError::Forbidden)));
+        assert!(unlisted_index.is_ok());
+        assert!(matches!(no_index, Err(HttpError::Forbidden)));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range("bytes=0-99", 1000),
+            RangeRequest::Satisfiable(0..100)
+        );
+        assert_eq!(
+            parse_range("bytes=900-", 1000),
+            RangeRequest::Satisfiable(900..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            RangeRequest::Satisfiable(900..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=0-5000", 1000),
+            RangeRequest::Satisfiable(0..1000)
+        );
+        assert_eq!(
+            parse_range(&format!("bytes=0-{}", u64::MAX), 1000),
+            RangeRequest::Satisfiable(0..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=1000-", 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(
+            parse_range("bytes=0-99,200-299", 1000),
+            RangeRequest::Ignored
+        );
+        assert_eq!(parse_range("bytes=99-0", 1000), RangeRequest::Ignored);
+        assert_eq!(parse_range("items=0-1", 1000), RangeRequest::Ignored);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::header::{Header, HeaderMap};
+use std::{net::IpAddr, str::FromStr};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("invalid CIDR range {0:?}")]
+pub struct InvalidCidr(String);
+
+/// A range of addresses like `10.0.0.0/8`, or a single address without a prefix length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 peers on a dual-stack socket show up as mapped IPv6 addresses
+        let ip = ip.to_canonical();
+
+        match (self.addr, ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                prefix_matches(&range.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                prefix_matches(&range.octets(), &ip.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether the first `prefix_len` bits of `range` and `ip` are equal
+fn prefix_matches(range: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let prefix_len = usize::from(prefix_len);
+    let (whole, rest) = (prefix_len / 8, prefix_len % 8);
+
+    if range[..whole] != ip[..whole] {
+        return false;
+    }
+
+    if rest == 0 {
+        return true;
+    }
+
+    let mask = 0xFF << (8 - rest);
+    range[whole] & mask == ip[whole] & mask
+}
+
+impl FromStr for Cidr {
+    type Err = InvalidCidr;
+
+    fn from_str(range: &str) -> Result<Cidr, InvalidCidr> {
+        let invalid = || InvalidCidr(range.to_owned());
+
+        let (addr, prefix_len) = match range.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (range, None),
+        };
+
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| invalid())?,
+            None => max_len,
+        };
+
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+
+        Ok(Cidr { addr, prefix_len })
+    }
+}
+
+/// An address from `X-Forwarded-For` or `X-Real-IP`, which some proxies send with a port or
+/// an IPv6 address in brackets, e.g. `203.0.113.7:4711` or `[2001:db8::1]:4711`
+fn parse_forwarded_addr(addr: &str) -> Option<IpAddr> {
+    let addr = addr.trim();
+
+    if let Ok(ip) = addr.parse::<IpAddr>() {
+        return Some(ip.to_canonical());
+    }
+
+    let ip = match addr.strip_prefix('[') {
+        Some(bracketed) => bracketed.split_once(']')?.0,
+        None => addr.split_once(':')?.0,
+    };
+
+    ip.parse::<IpAddr>().ok().map(|ip| ip.to_canonical())
+}
+
+/// Which peers may report the client address in `X-Forwarded-For` or `X-Real-IP`, nobody by
+/// default since any client can send those headers
+#[derive(Debug, Clone, Default)]
+pub struct TrustProxy {
+    pub trusted: Vec<Cidr>,
+}
+
+impl TrustProxy {
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|range| range.contains(ip))
+    }
+
+    /// The client address reported by `peer`, `None` if it isn't a trusted proxy or reported
+    /// no valid address
+    ///
+    /// `X-Forwarded-For` is walked from the right, since every proxy appends the address it
+    /// got the request from and only entries added by trusted proxies can be believed. The
+    /// first untrusted address is the client, or the first one if the whole chain is trusted.
+    pub fn forwarded_for(&self, peer: IpAddr, headers: &HeaderMap) -> Option<IpAddr> {
+        if !self.is_trusted(peer) {
+            return None;
+        }
+
+        let forwarded_for: Vec<_> = headers
+            .get_all(&Header::Custom("X-Forwarded-For".to_owned()))
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(parse_forwarded_addr)
+            .collect();
+
+        if !forwarded_for.is_empty() {
+            let mut client = None;
+
+            for addr in forwarded_for.into_iter().rev() {
+                // Nothing left of a garbled entry can be believed, fall back to the peer
+                let addr = addr?;
+                client = Some(addr);
+
+                if !self.is_trusted(addr) {
+                    break;
+                }
+            }
+
+            return client;
+        }
+
+        headers
+            .get_first(&Header::Custom("X-Real-IP".to_owned()))
+            .and_then(parse_forwarded_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().expect("parse ip")
+    }
+
+    #[test]
+    fn test_cidr() {
+        let range: Cidr = "10.1.0.0/16".parse().expect("parse cidr");
+        assert!(range.contains(ip("10.1.200.3")));
+        assert!(range.contains(ip("::ffff:10.1.0.1")));
+        assert!(!range.contains(ip("10.2.0.1")));
+
+        let range: Cidr = "fd00::/7".parse().expect("parse cidr");
+        assert!(range.contains(ip("fdab::1")));
+        assert!(!range.contains(ip("fe80::1")));
+
+        let host: Cidr = "127.0.0.1".parse().expect("parse cidr");
+        assert!(host.contains(ip("127.0.0.1")));
+        assert!(!host.contains(ip("127.0.0.2")));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("localhost".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_parse_forwarded_addr() {
+        assert_eq!(
+            parse_forwarded_addr(" 2001:db8::1 "),
+            Some(ip("2001:db8::1"))
+        );
+        assert_eq!(
+            parse_forwarded_addr("[2001:db8::1]"),
+            Some(ip("2001:db8::1"))
+        );
+        assert_eq!(
+            parse_forwarded_addr("[2001:db8::1]:80"),
+            Some(ip("2001:db8::1"))
+        );
+        assert_eq!(
+            parse_forwarded_addr("203.0.113.7:80"),
+            Some(ip("203.0.113.7"))
+        );
+        assert_eq!(
+            parse_forwarded_addr("::ffff:203.0.113.7"),
+            Some(ip("203.0.113.7"))
+        );
+        assert_eq!(parse_forwarded_addr("unknown"), None);
+    }
+
+    #[test]
+    fn test_forwarded_for() {
+        let trust = TrustProxy {
+            trusted: vec!["127.0.0.1".parse().expect("parse cidr")],
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            Header::Custom("x-forwarded-for".to_owned()),
+            "6.6.6.6, 203.0.113.7, 127.0.0.1",
+        );
+
+        // The client can only prepend to the list, so the spoofed address is skipped
+        assert_eq!(
+            trust.forwarded_for(ip("127.0.0.1"), &headers),
+            Some(ip("203.0.113.7"))
+        );
+        assert_eq!(trust.forwarded_for(ip("198.51.100.1"), &headers), None);
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            Header::Custom("X-Forwarded-For".to_owned()),
+            "[2001:db8::7]:4711, ::1",
+        );
+        let trust = TrustProxy {
+            trusted: vec![
+                "127.0.0.1".parse().expect("parse cidr"),
+                "::1".parse().expect("parse cidr"),
+            ],
+        };
+        assert_eq!(
+            trust.forwarded_for(ip("::1"), &headers),
+            Some(ip("2001:db8::7"))
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.append(Header::Custom("X-Real-IP".to_owned()), "203.0.113.7");
+        assert_eq!(
+            trust.forwarded_for(ip("127.0.0.1"), &headers),
+            Some(ip("203.0.113.7"))
+        );
+    }
+}
@@ -0,0 +1,580 @@
+use crate::request::log_parse_error;
+use nom::{
+    bytes::complete::take_while1,
+    character::complete::{char, space1},
+    combinator::{all_consuming, rest, verify},
+    sequence::{preceded, separated_pair},
+    IResult, Parser,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    hash::{Hash, Hasher},
+    iter, mem,
+    ops::Index,
+    slice,
+};
+
+/// A header name, compared case-insensitively as required by RFC 7230
+#[derive(Debug, Clone, Eq)]
+pub enum Header {
+    UserAgent,
+    ContentType,
+    ContentLength,
+    TransferEncoding,
+    AcceptEncoding,
+    ContentEncoding,
+    Connection,
+    Origin,
+    AccessControlRequestMethod,
+    AccessControlRequestHeaders,
+    AccessControlAllowOrigin,
+    AccessControlAllowMethods,
+    AccessControlAllowHeaders,
+    ETag,
+    IfNoneMatch,
+    RetryAfter,
+    ContentDisposition,
+    Cookie,
+    SetCookie,
+    Authorization,
+    WwwAuthenticate,
+    CacheControl,
+    Expires,
+    Date,
+    LastModified,
+    Range,
+    ContentRange,
+    AcceptRanges,
+    Upgrade,
+    SecWebSocketKey,
+    SecWebSocketAccept,
+    SecWebSocketVersion,
+    Accept,
+    IfModifiedSince,
+    Location,
+    Vary,
+    Host,
+    Expect,
+    RequestId,
+    Via,
+    Allow,
+    SecFetchSite,
+    SecFetchMode,
+    SecFetchDest,
+    Server,
+    ServerTiming,
+    /// Any other header, keeping the name as it was received
+    Custom(String),
+}
+
+/// `tchar` from RFC 7230, the characters allowed in a header name
+fn is_token_char(char: char) -> bool {
+    char.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(char)
+}
+
+impl Header {
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub fn parse(input: &str) -> IResult<&str, Header> {
+        let mut parser = take_while1(is_token_char).map(Header::from_name);
+
+        log_parse_error("header name", parser.parse(input))
+    }
+
+    /// The known header called `name` in any casing, or a [`Header::Custom`]
+    pub fn from_name(name: &str) -> Header {
+        match name.to_ascii_lowercase().as_str() {
+            "user-agent" => Header::UserAgent,
+            "content-type" => Header::ContentType,
+            "content-length" => Header::ContentLength,
+            "transfer-encoding" => Header::TransferEncoding,
+            "accept-encoding" => Header::AcceptEncoding,
+            "content-encoding" => Header::ContentEncoding,
+            "connection" => Header::Connection,
+            "origin" => Header::Origin,
+            "access-control-request-method" => Header::AccessControlRequestMethod,
+            "access-control-request-headers" => Header::AccessControlRequestHeaders,
+            "access-control-allow-origin" => Header::AccessControlAllowOrigin,
+            "access-control-allow-methods" => Header::AccessControlAllowMethods,
+            "access-control-allow-headers" => Header::AccessControlAllowHeaders,
+            "etag" => Header::ETag,
+            "if-none-match" => Header::IfNoneMatch,
+            "retry-after" => Header::RetryAfter,
+            "content-disposition" => Header::ContentDisposition,
+            "cookie" => Header::Cookie,
+            "set-cookie" => Header::SetCookie,
+            "authorization" => Header::Authorization,
+            "www-authenticate" => Header::WwwAuthenticate,
+            "cache-control" => Header::CacheControl,
+            "expires" => Header::Expires,
+            "date" => Header::Date,
+            "last-modified" => Header::LastModified,
+            "range" => Header::Range,
+            "content-range" => Header::ContentRange,
+            "accept-ranges" => Header::AcceptRanges,
+            "upgrade" => Header::Upgrade,
+            "sec-websocket-key" => Header::SecWebSocketKey,
+            "sec-websocket-accept" => Header::SecWebSocketAccept,
+            "sec-websocket-version" => Header::SecWebSocketVersion,
+            "accept" => Header::Accept,
+            "if-modified-since" => Header::IfModifiedSince,
+            "location" => Header::Location,
+            "vary" => Header::Vary,
+            "host" => Header::Host,
+            "expect" => Header::Expect,
+            "x-request-id" => Header::RequestId,
+            "via" => Header::Via,
+            "allow" => Header::Allow,
+            "sec-fetch-site" => Header::SecFetchSite,
+            "sec-fetch-mode" => Header::SecFetchMode,
+            "sec-fetch-dest" => Header::SecFetchDest,
+            "server" => Header::Server,
+            "server-timing" => Header::ServerTiming,
+            _ => Header::Custom(name.to_owned()),
+        }
+    }
+
+    /// Whether the header's value is a comma-separated list that may be split into values
+    pub fn is_list(&self) -> bool {
+        matches!(
+            self,
+            Header::TransferEncoding
+                | Header::AcceptEncoding
+                | Header::Connection
+                | Header::AccessControlRequestHeaders
+                | Header::IfNoneMatch
+                | Header::CacheControl
+                | Header::Upgrade
+                | Header::Accept
+                | Header::Vary
+                | Header::Via
+        )
+    }
+}
+
+impl Display for Header {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Header::UserAgent => write!(f, "User-Agent"),
+            Header::ContentType => write!(f, "Content-Type"),
+            Header::ContentLength => write!(f, "Content-Length"),
+            Header::TransferEncoding => write!(f, "Transfer-Encoding"),
+            Header::AcceptEncoding => write!(f, "Accept-Encoding"),
+            Header::ContentEncoding => write!(f, "Content-Encoding"),
+            Header::Connection => write!(f, "Connection"),
+            Header::Origin => write!(f, "Origin"),
+            Header::AccessControlRequestMethod => write!(f, "Access-Control-Request-Method"),
+            Header::AccessControlRequestHeaders => write!(f, "Access-Control-Request-Headers"),
+            Header::AccessControlAllowOrigin => write!(f, "Access-Control-Allow-Origin"),
+            Header::AccessControlAllowMethods => write!(f, "Access-Control-Allow-Methods"),
+            Header::AccessControlAllowHeaders => write!(f, "Access-Control-Allow-Headers"),
+            Header::ETag => write!(f, "ETag"),
+            Header::IfNoneMatch => write!(f, "If-None-Match"),
+            Header::RetryAfter => write!(f, "Retry-After"),
+            Header::ContentDisposition => write!(f, "Content-Disposition"),
+            Header::Cookie => write!(f, "Cookie"),
+            Header::SetCookie => write!(f, "Set-Cookie"),
+            Header::Authorization => write!(f, "Authorization"),
+            Header::WwwAuthenticate => write!(f, "WWW-Authenticate"),
+            Header::CacheControl => write!(f, "Cache-Control"),
+            Header::Expires => write!(f, "Expires"),
+            Header::Date => write!(f, "Date"),
+            Header::LastModified => write!(f, "Last-Modified"),
+            Header::Range => write!(f, "Range"),
+            Header::ContentRange => write!(f, "Content-Range"),
+            Header::AcceptRanges => write!(f, "Accept-Ranges"),
+            Header::Upgrade => write!(f, "Upgrade"),
+            Header::SecWebSocketKey => write!(f, "Sec-WebSocket-Key"),
+            Header::SecWebSocketAccept => write!(f, "Sec-WebSocket-Accept"),
+            Header::SecWebSocketVersion => write!(f, "Sec-WebSocket-Version"),
+            Header::Accept => write!(f, "Accept"),
+            Header::IfModifiedSince => write!(f, "If-Modified-Since"),
+            Header::Location => write!(f, "Location"),
+            Header::Vary => write!(f, "Vary"),
+            Header::Host => write!(f, "Host"),
+            Header::Expect => write!(f, "Expect"),
+            Header::RequestId => write!(f, "X-Request-Id"),
+            Header::Via => write!(f, "Via"),
+            Header::Allow => write!(f, "Allow"),
+            Header::SecFetchSite => write!(f, "Sec-Fetch-Site"),
+            Header::SecFetchMode => write!(f, "Sec-Fetch-Mode"),
+            Header::SecFetchDest => write!(f, "Sec-Fetch-Dest"),
+            Header::Server => write!(f, "Server"),
+            Header::ServerTiming => write!(f, "Server-Timing"),
+            Header::Custom(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl PartialEq for Header {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Header::Custom(name), Header::Custom(other)) => name.eq_ignore_ascii_case(other),
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+
+impl Hash for Header {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        if let Header::Custom(name) = self {
+            for byte in name.bytes() {
+                state.write_u8(byte.to_ascii_lowercase());
+            }
+        }
+    }
+}
+
+/// Request headers, where a header can occur multiple times or hold a comma-separated list
+#[derive(Debug, Default)]
+pub struct HeaderMap(HashMap<Header, Vec<String>>);
+
+impl HeaderMap {
+    pub fn new() -> HeaderMap {
+        HeaderMap::default()
+    }
+
+    /// Adds `value` to the existing values of `header`, splitting it if the header is a list
+    pub fn append(&mut self, header: Header, value: &str) {
+        let values = self.0.entry(header.clone()).or_default();
+
+        if header.is_list() {
+            let items = value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty());
+            values.extend(items.map(str::to_owned));
+        } else {
+            values.push(value.to_owned());
+        }
+    }
+
+    /// Replaces all values of `header` with `value`
+    pub fn insert(&mut self, header: Header, value: impl Into<String>) {
+        self.0.insert(header, vec![value.into()]);
+    }
+
+    pub fn get_first(&self, header: &Header) -> Option<&str> {
+        self.get_all(header).first().map(String::as_str)
+    }
+
+    pub fn get_all(&self, header: &Header) -> &[String] {
+        self.0.get(header).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Removes all values of `header`, returning them
+    pub fn remove(&mut self, header: &Header) -> Vec<String> {
+        self.0.remove(header).unwrap_or_default()
+    }
+
+    pub fn contains(&self, header: &Header) -> bool {
+        self.0.contains_key(header)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Header, &String)> {
+        self.0
+            .iter()
+            .flat_map(|(header, values)| values.iter().map(move |value| (header, value)))
+    }
+}
+
+/// Response headers in the order they were added, one value per header line
+///
+/// Most headers are set once, replacing any earlier value, but some are sent once per value
+/// and must be [appended](ResponseHeaders::append) instead, such as `Set-Cookie`, whose
+/// values can't be joined with commas like a list, `Link` and `Warning`.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseHeaders(Vec<(Header, String)>);
+
+impl ResponseHeaders {
+    pub fn new() -> ResponseHeaders {
+        ResponseHeaders::default()
+    }
+
+    /// Adds another `header` line, keeping the ones already there
+    pub fn append(&mut self, header: Header, value: impl Into<String>) {
+        self.0.push((header, value.into()));
+    }
+
+    /// Replaces all values of `header` with `value`, where the first of them was
+    pub fn insert(&mut self, header: Header, value: impl Into<String>) {
+        let value = value.into();
+
+        match self.0.iter().position(|(existing, _)| *existing == header) {
+            Some(index) => {
+                self.0[index].1 = value;
+
+                let mut position = 0;
+                self.0.retain(|(existing, _)| {
+                    position += 1;
+                    position <= index + 1 || *existing != header
+                });
+            }
+            None => self.0.push((header, value)),
+        }
+    }
+
+    /// The first value of `header`
+    pub fn get(&self, header: &Header) -> Option<&String> {
+        self.0
+            .iter()
+            .find(|(existing, _)| existing == header)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, header: &Header) -> Option<&mut String> {
+        self.0
+            .iter_mut()
+            .find(|(existing, _)| existing == header)
+            .map(|(_, value)| value)
+    }
+
+    /// The first value of `header`, inserting `default()` if there's none
+    pub fn get_or_insert_with(
+        &mut self,
+        header: Header,
+        default: impl FnOnce() -> String,
+    ) -> &mut String {
+        let index = match self.0.iter().position(|(existing, _)| *existing == header) {
+            Some(index) => index,
+            None => {
+                self.0.push((header, default()));
+                self.0.len() - 1
+            }
+        };
+
+        &mut self.0[index].1
+    }
+
+    pub fn get_all<'a>(&'a self, header: &'a Header) -> impl Iterator<Item = &'a String> {
+        self.0
+            .iter()
+            .filter(move |(existing, _)| existing == header)
+            .map(|(_, value)| value)
+    }
+
+    /// Removes all values of `header`, returning the first
+    pub fn remove(&mut self, header: &Header) -> Option<String> {
+        let first = self
+            .0
+            .iter()
+            .position(|(existing, _)| existing == header)
+            .map(|index| self.0.remove(index).1);
+
+        self.0.retain(|(existing, _)| existing != header);
+
+        first
+    }
+
+    pub fn contains_key(&self, header: &Header) -> bool {
+        self.0.iter().any(|(existing, _)| existing == header)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Header, &String)> {
+        self.into_iter()
+    }
+
+    /// The number of header lines
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a ResponseHeaders {
+    type Item = (&'a Header, &'a String);
+    type IntoIter = iter::Map<slice::Iter<'a, (Header, String)>, HeaderLine<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter().map(|(header, value)| (header, value))
+    }
+}
+
+/// The first value of `header`, panicking if there's none
+impl Index<&Header> for ResponseHeaders {
+    type Output = String;
+
+    fn index(&self, header: &Header) -> &String {
+        self.get(header)
+            .unwrap_or_else(|| panic!("no {header} header"))
+    }
+}
+
+type HeaderLine<'a> = fn(&'a (Header, String)) -> (&'a Header, &'a String);
+
+/// Whether `char` may appear in a header value, any visible character, whitespace or
+/// `obs-text`, but no control characters that could end the header early
+fn is_value_char(char: char) -> bool {
+    char == '\t' || !char.is_control()
+}
+
+/// Parses a `name: value` header line, with the optional whitespace around the value trimmed
+///
+/// Whitespace between the name and the colon is rejected rather than trimmed, as servers and
+/// proxies disagreeing about such a header is how requests get smuggled.
+#[tracing::instrument(level = "trace", skip_all)]
+pub fn parse_header_value(line: &str) -> IResult<&str, (Header, &str)> {
+    let value = verify(rest, |value: &str| value.chars().all(is_value_char))
+        .map(|value: &str| value.trim_matches([' ', '\t']));
+
+    let mut parser = all_consuming(separated_pair(Header::parse, char(':'), value));
+
+    log_parse_error("header line", parser.parse(line))
+}
+
+/// Parses a line continuing the value of the header before it, which starts with a space or
+/// tab (the obsolete line folding of RFC 7230), with the whitespace around it trimmed
+#[tracing::instrument(level = "trace", skip_all)]
+pub fn parse_folded_line(line: &str) -> IResult<&str, &str> {
+    let value = verify(rest, |value: &str| value.chars().all(is_value_char))
+        .map(|value: &str| value.trim_matches([' ', '\t']));
+
+    let mut parser = all_consuming(preceded(space1, value));
+
+    log_parse_error("folded header line", parser.parse(line))
+}
+
+/// Joins the lines of a folded header value with single spaces, as RFC 7230 says each fold
+/// is to be replaced with, leaving out lines with nothing on them
+pub fn unfold_header_value(lines: &[&str]) -> String {
+    lines
+        .iter()
+        .map(|line| line.trim_matches([' ', '\t']))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_known_header() {
+        let (_, (header, value)) = parse_header_value("user-agent: curl").expect("parse header");
+
+        assert_eq!(header, Header::UserAgent);
+        assert_eq!(value, "curl");
+    }
+
+    #[test]
+    fn test_parse_header_whitespace() {
+        for line in [
+            "Host:example.com",
+            "Host: example.com",
+            "Host:\t example.com \t",
+        ] {
+            let (_, (header, value)) = parse_header_value(line).expect("parse header");
+
+            assert_eq!(header, Header::Host);
+            assert_eq!(value, "example.com");
+        }
+
+        let (_, (_, value)) = parse_header_value("X-Empty:").expect("parse header");
+        assert_eq!(value, "");
+
+        for line in [
+            "Host : example.com",
+            "Host\t: example.com",
+            " Host: example.com",
+            "Bad Name: value",
+            ": value",
+            "Host example.com",
+            "X-Nul: a\0b",
+            "X-Cr: a\rb",
+        ] {
+            assert!(parse_header_value(line).is_err(), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn test_unfold_header_value() {
+        assert_eq!(parse_folded_line(" \tmore text "), Ok(("", "more text")));
+        assert!(parse_folded_line("not folded").is_err());
+        assert!(parse_folded_line(" a\0b").is_err());
+
+        assert_eq!(unfold_header_value(&["a", "b c", "", "d"]), "a b c d");
+        assert_eq!(unfold_header_value(&["", "b"]), "b");
+    }
+
+    #[test]
+    fn test_custom_header_round_trip() {
+        let line = "X-Forwarded-For: 203.0.113.7";
+        let (_, (header, value)) = parse_header_value(line).expect("parse header");
+
+        assert_eq!(header, Header::Custom("x-forwarded-for".to_owned()));
+        assert_eq!(format!("{header}: {value}"), line);
+    }
+
+    #[test]
+    fn test_header_map() {
+        let mut headers = HeaderMap::new();
+        headers.append(Header::AcceptEncoding, "gzip, deflate");
+        headers.append(Header::AcceptEncoding, "br");
+        headers.append(Header::UserAgent, "curl/8.0, really");
+
+        assert_eq!(
+            headers.get_all(&Header::AcceptEncoding),
+            ["gzip", "deflate", "br"]
+        );
+        assert_eq!(
+            headers.get_first(&Header::UserAgent),
+            Some("curl/8.0, really")
+        );
+        assert!(headers.contains(&Header::UserAgent));
+        assert!(!headers.contains(&Header::Origin));
+        assert!(headers.get_all(&Header::Origin).is_empty());
+    }
+
+    #[test]
+    fn test_response_headers() {
+        let mut headers = ResponseHeaders::new();
+        headers.append(Header::SetCookie, "a=1");
+        headers.insert(Header::ContentType, "text/plain");
+        headers.append(Header::SetCookie, "b=2");
+
+        let lines = |headers: &ResponseHeaders| {
+            headers
+                .iter()
+                .map(|(header, value)| format!("{header}: {value}"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            lines(&headers),
+            [
+                "Set-Cookie: a=1",
+                "Content-Type: text/plain",
+                "Set-Cookie: b=2"
+            ]
+        );
+        assert_eq!(
+            headers.get_all(&Header::SetCookie).collect::<Vec<_>>(),
+            ["a=1", "b=2"]
+        );
+        assert_eq!(headers.get(&Header::SetCookie), Some(&"a=1".to_owned()));
+
+        // Replacing keeps the place of the first value
+        headers.insert(Header::SetCookie, "c=3");
+        assert_eq!(
+            lines(&headers),
+            ["Set-Cookie: c=3", "Content-Type: text/plain"]
+        );
+
+        *headers.get_or_insert_with(Header::Vary, || "Accept".to_owned()) += ", Origin";
+        headers.get_or_insert_with(Header::Vary, || unreachable!());
+        assert_eq!(
+            headers.get(&Header::Vary),
+            Some(&"Accept, Origin".to_owned())
+        );
+
+        assert_eq!(headers.remove(&Header::SetCookie), Some("c=3".to_owned()));
+        assert!(!headers.contains_key(&Header::SetCookie));
+        assert_eq!(headers.len(), 2);
+    }
+}
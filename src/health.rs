@@ -0,0 +1,103 @@
+use crate::{
+    response::{Response, Status},
+    router::Router,
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+/// Load figures reported by `GET /health`
+#[derive(Debug)]
+pub struct Metrics {
+    active_connections: AtomicUsize,
+    started: Instant,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            active_connections: AtomicUsize::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    /// Counts a connection as active until the returned guard is dropped
+    pub fn connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+
+        ConnectionGuard(self.clone())
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "status": "ok",
+            "active_connections": self.active_connections(),
+            "uptime_secs": self.started.elapsed().as_secs(),
+        })
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// Marks a connection counted by [`Metrics::connection`] as closed when dropped
+#[derive(Debug)]
+pub struct ConnectionGuard(Arc<Metrics>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Registers `GET /health`, after any routes already on `router` so they take precedence
+pub fn routes(router: Router, metrics: Arc<Metrics>) -> Router {
+    router.get("/health", move |_, _| {
+        let report = metrics.report();
+
+        async move {
+            Response::json(&report).unwrap_or_else(|err| {
+                tracing::error!("error serializing health report: {err:?}");
+                Response::empty(Status::InternalServerError)
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{Method, Request};
+
+    #[tokio::test]
+    async fn test_health() {
+        let metrics = Arc::new(Metrics::new());
+        let router = routes(Router::new(), metrics.clone());
+
+        let first = metrics.connection();
+        let second = metrics.connection();
+        drop(first);
+
+        let response = router.handle(Request::new(Method::GET, "/health")).await;
+        assert_eq!(response.status, Status::Ok);
+
+        let body = response.body.as_bytes().expect("sized body");
+        let report: serde_json::Value = serde_json::from_slice(body).expect("parse body");
+        assert_eq!(report["status"], "ok");
+        assert_eq!(report["active_connections"], 1);
+
+        drop(second);
+        assert_eq!(metrics.active_connections(), 0);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::{
+    config::ServerConfig,
+    header::{Header, HeaderMap},
+    rate_limit::RateLimiter,
+    request::{HttpVersion, Method, Request, RequestError},
+    response::{Response, ResponseBody, Status},
+    router::Router,
+    server::{answer, finish, identify},
+    timing::Timings,
+};
+use anyhow::Context;
+use bytes::Bytes;
+use h2::{
+    server::{self, SendResponse},
+    RecvStream, SendStream,
+};
+use std::{future::poll_fn, net::SocketAddr, sync::Arc, time::Instant};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite},
+    task::JoinSet,
+    time::{sleep, timeout},
+};
+use tracing::Instrument;
+
+/// Most of a streamed body read at a time, each read is sent as one DATA frame
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Answers the streams of an HTTP/2 connection until it's closed, each in a task of its own
+///
+/// Like an HTTP/1.1 connection it's closed once it has been idle for
+/// [`ServerConfig::keep_alive_timeout`].
+pub(crate) async fn serve<S>(
+    stream: S,
+    remote_addr: Option<SocketAddr>,
+    router: Arc<Router>,
+    rate_limiter: Arc<RateLimiter>,
+    config: Arc<ServerConfig>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let mut connection = timeout(config.connection_timeout, server::handshake(stream))
+        .await
+        .context("http/2 handshake timed out")?
+        .context("http/2 handshake")?;
+
+    let mut streams = JoinSet::new();
+    let mut closing = false;
+
+    loop {
+        tokio::select! {
+            accepted = connection.accept() => {
+                let Some(accepted) = accepted else {
+                    return Ok(());
+                };
+
+                let (request, respond) = accepted.context("accept http/2 stream")?;
+
+                let router = router.clone();
+                let rate_limiter = rate_limiter.clone();
+                let config = config.clone();
+
+                streams.spawn(async move {
+                    answer_stream(request, respond, remote_addr, &router, &rate_limiter, &config)
+                        .await
+                });
+            }
+            // Reap finished streams so the connection can tell when it's idle
+            Some(_) = streams.join_next() => {}
+            _ = sleep(config.keep_alive_timeout), if streams.is_empty() && !closing => {
+                // Lets the client know no more streams are accepted, then waits for it to
+                // close the connection
+                connection.graceful_shutdown();
+                closing = true;
+            }
+        }
+    }
+}
+
+/// Answers the request on a single stream, the way [`crate::server`] answers a request on
+/// an HTTP/1.1 connection
+async fn answer_stream(
+    request: http::Request<RecvStream>,
+    respond: SendResponse<Bytes>,
+    remote_addr: Option<SocketAddr>,
+    router: &Router,
+    rate_limiter: &RateLimiter,
+    config: &ServerConfig,
+) {
+    let start = Instant::now();
+    let mut timings = Timings::new();
+    let (parts, body) = request.into_parts();
+    let head = parts.method == http::Method::HEAD;
+
+    let mut request = match to_request(parts, config) {
+        Ok(request) => request,
+        Err(err) => {
+            tracing::debug!("invalid http/2 request: {err}");
+
+            let status = err.status().unwrap_or(Status::BadRequest);
+            let _ = send(respond, Response::empty(status), head).await;
+            return;
+        }
+    };
+
+    let id = identify(&mut request, remote_addr, config);
+    let span = tracing::info_span!("request", %id);
+
+    span.in_scope(|| tracing::debug!(method = ?request.method, path = %request.path, "request"));
+
+    let answered = async {
+        request.check_host()?;
+
+        let body = timeout(
+            config.read_body_timeout,
+            read_body(body, config.max_body_bytes),
+        )
+        .await
+        .map_err(|_| RequestError::BodyTimeout)??;
+        request.set_body(body, config)?;
+        timings.record("parse");
+
+        let response = answer(request, router, rate_limiter, config).await;
+        timings.record("handler");
+
+        Ok::<_, RequestError>(response)
+    };
+
+    let mut response = match answered.instrument(span.clone()).await {
+        Ok(response) => response,
+        Err(err) => {
+            span.in_scope(|| tracing::debug!("invalid http/2 request: {err}"));
+            Response::empty(err.status().unwrap_or(Status::BadRequest))
+        }
+    };
+
+    finish(&mut response, &id, config);
+
+    timings.record("serialize");
+    if config.server_timing {
+        response
+            .headers
+            .insert(Header::ServerTiming, timings.to_string());
+    }
+
+    let status = response.status;
+
+    match send(respond, response, head).instrument(span.clone()).await {
+        Ok(()) => span.in_scope(|| tracing::info!(%status, latency = ?start.elapsed(), "response")),
+        Err(err) => span.in_scope(|| tracing::debug!("send http/2 response: {err:#}")),
+    }
+}
+
+/// The server's own request for the head of an HTTP/2 request, the body is read separately
+fn to_request(parts: http::request::Parts, config: &ServerConfig) -> Result<Request, RequestError> {
+    if parts.headers.len() > config.max_header_count {
+        return Err(RequestError::TooManyHeaders(config.max_header_count));
+    }
+
+    let invalid = || RequestError::InvalidRequestLine(format!("{} {}", parts.method, parts.uri));
+
+    let method = match Method::parse(parts.method.as_str()) {
+        Ok(("", method)) => method,
+        _ => return Err(invalid()),
+    };
+
+    // `CONNECT` only has an authority
+    let target = match parts.uri.path_and_query() {
+        Some(path) => path.as_str().to_owned(),
+        None => parts.uri.authority().ok_or_else(invalid)?.to_string(),
+    };
+
+    let mut headers = HeaderMap::new();
+
+    for (name, value) in &parts.headers {
+        let value = value
+            .to_str()
+            .map_err(|_| RequestError::InvalidHeader(name.to_string()))?;
+
+        headers.append(Header::from_name(name.as_str()), value);
+    }
+
+    // HTTP/2 sends the host as the `:authority` pseudo-header instead
+    if !headers.contains(&Header::Host) {
+        if let Some(authority) = parts.uri.authority() {
+            headers.append(Header::Host, authority.as_str());
+        }
+    }
+
+    Request::from_head(method, target, HttpVersion::HTTP_2, headers)
+}
+
+/// Reads the DATA frames of a request, up to `max_body_bytes`
+async fn read_body(mut body: RecvStream, max_body_bytes: usize) -> Result<Vec<u8>, RequestError> {
+    let mut buf = Vec::new();
+
+    while let Some(data) = body.data().await {
+        let data = data.map_err(|_| RequestError::Truncated)?;
+
+        // Lets the client send more
+        let _ = body.flow_control().release_capacity(data.len());
+
+        if buf.len() + data.len() > max_body_bytes {
+            return Err(RequestError::BodyTooLarge(buf.len() + data.len()));
+        }
+
+        buf.extend_from_slice(&data);
+    }
+
+    Ok(buf)
+}
+
+/// Sends `response` on its stream, only the headers in answer to a HEAD request
+async fn send(
+    mut respond: SendResponse<Bytes>,
+    response: Response,
+    head: bool,
+) -> anyhow::Result<()> {
+    let empty = match &response.body {
+        ResponseBody::Sized(body) => body.is_empty(),
+        ResponseBody::Chunked(_) => false,
+    };
+    let end_of_stream = head || empty || !response.status.has_body();
+
+    let mut stream = respond
+        .send_response(to_http(&response)?, end_of_stream)
+        .context("send headers")?;
+
+    if end_of_stream {
+        return Ok(());
+    }
+
+    match response.body {
+        ResponseBody::Sized(body) => send_data(&mut stream, body.into(), true).await,
+        ResponseBody::Chunked(mut reader) => loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let len = reader.read(&mut chunk).await.context("read body")?;
+            chunk.truncate(len);
+
+            send_data(&mut stream, chunk.into(), len == 0).await?;
+
+            if len == 0 {
+                return Ok(());
+            }
+        },
+    }
+}
+
+/// The head of `response` as an HTTP/2 response
+fn to_http(response: &Response) -> anyhow::Result<http::Response<()>> {
+    let mut builder = http::Response::builder().status(response.status.code());
+
+    for (header, value) in &response.headers {
+        // HTTP/2 frames messages itself and doesn't allow per-connection headers
+        if matches!(
+            header,
+            Header::Connection | Header::TransferEncoding | Header::Upgrade
+        ) || matches!(header, Header::Custom(name) if name.eq_ignore_ascii_case("keep-alive"))
+        {
+            continue;
+        }
+
+        // The length of a streamed body isn't known up front, and some statuses have no body
+        if *header == Header::ContentLength
+            && (response.body.as_bytes().is_none() || !response.status.has_body())
+        {
+            continue;
+        }
+
+        builder = builder.header(header.to_string(), value);
+    }
+
+    if let ResponseBody::Sized(body) = &response.body {
+        if response.status.has_body() && !response.headers.contains_key(&Header::ContentLength) {
+            builder = builder.header(Header::ContentLength.to_string(), body.len());
+        }
+    }
+
+    builder.body(()).context("build http/2 response")
+}
+
+/// Sends `data` as DATA frames, as fast as the client's flow control window allows
+async fn send_data(
+    stream: &mut SendStream<Bytes>,
+    mut data: Bytes,
+    end: bool,
+) -> anyhow::Result<()> {
+    if data.is_empty() {
+        return stream.send_data(data, end).context("send body");
+    }
+
+    while !data.is_empty() {
+        stream.reserve_capacity(data.len());
+
+        let capacity = poll_fn(|cx| stream.poll_capacity(cx))
+            .await
+            .context("stream closed")?
+            .context("wait for capacity")?;
+
+        let chunk = data.split_to(capacity.min(data.len()));
+        stream
+            .send_data(chunk, end && data.is_empty())
+            .context("send body")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serve() {
+        let router = Router::new().post("/echo", |request: Request, _| async move {
+            Response::builder().body(request.body).build()
+        });
+
+        let config = Arc::new(ServerConfig::default());
+        let rate_limiter = Arc::new(RateLimiter::from_config(&config));
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(serve(server, None, Arc::new(router), rate_limiter, config));
+
+        let (mut client, connection) = h2::client::handshake(client).await.expect("handshake");
+        tokio::spawn(connection);
+
+        let request = http::Request::post("https://localhost/echo")
+            .body(())
+            .expect("build request");
+        let (response, mut body) = client.send_request(request, false).expect("send request");
+        body.send_data(Bytes::from_static(b"hello"), true)
+            .expect("send body");
+
+        let response = response.await.expect("response");
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-length"], "5");
+        assert!(response.headers().contains_key("x-request-id"));
+
+        let mut body = response.into_body();
+        let mut received = Vec::new();
+        while let Some(data) = body.data().await {
+            received.extend_from_slice(&data.expect("read body"));
+        }
+        assert_eq!(received, b"hello");
+
+        let request = http::Request::get("https://localhost/missing")
+            .body(())
+            .expect("build request");
+        let (response, _) = client.send_request(request, true).expect("send request");
+        assert_eq!(response.await.expect("response").status(), 404);
+    }
+}
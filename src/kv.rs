@@ -0,0 +1,175 @@
+use crate::{
+    error::HttpError,
+    header::Header,
+    request::Request,
+    response::{Response, Status},
+    router::{Params, Router},
+};
+use anyhow::Context;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// The media type of a JSON merge patch
+const MERGE_PATCH: &str = "application/merge-patch+json";
+
+/// Values stored by the `/kv` routes, shared by every connection
+pub type Store = Arc<RwLock<HashMap<String, Vec<u8>>>>;
+
+/// Registers an in-memory key-value store on `router`
+///
+/// `GET /kv/:key` answers the stored value, `PUT /kv/:key` stores the body and
+/// `DELETE /kv/:key` removes it. `GET /kv` lists the keys as a sorted JSON array.
+///
+/// `PATCH /kv/:key` applies a JSON merge patch from RFC 7396 to a JSON value and answers the
+/// result.
+pub fn routes(router: Router, store: Store) -> Router {
+    let get_store = store.clone();
+    let put_store = store.clone();
+    let patch_store = store.clone();
+    let delete_store = store.clone();
+
+    router
+        .get("/kv", move |_, _| {
+            let response = list(&store);
+            async move { response }
+        })
+        .get("/kv/:key", move |_, params| {
+            let response = get(&get_store, &params);
+            async move { response }
+        })
+        .put("/kv/:key", move |request, params| {
+            let response = put(&put_store, request, &params);
+            async move { response }
+        })
+        .patch("/kv/:key", move |request, params| {
+            let response = patch(&patch_store, request, &params);
+            async move { response }
+        })
+        .delete("/kv/:key", move |_, params| {
+            let response = delete(&delete_store, &params);
+            async move { response }
+        })
+}
+
+fn key(params: &Params) -> Result<&str, HttpError> {
+    params.get("key").ok_or(HttpError::NotFound)
+}
+
+fn list(store: &Store) -> Result<Response, HttpError> {
+    let store = store.read().unwrap_or_else(|err| err.into_inner());
+
+    let mut keys: Vec<_> = store.keys().collect();
+    keys.sort();
+
+    Ok(Response::json(&keys).context("serialize keys")?)
+}
+
+fn get(store: &Store, params: &Params) -> Result<Response, HttpError> {
+    let store = store.read().unwrap_or_else(|err| err.into_inner());
+    let value = store.get(key(params)?).ok_or(HttpError::NotFound)?;
+
+    Ok(Response::builder()
+        .header(Header::ContentType, "application/octet-stream")
+        .body(value.clone())
+        .build())
+}
+
+fn put(store: &Store, request: Request, params: &Params) -> Result<Response, HttpError> {
+    let key = key(params)?.to_owned();
+
+    let mut store = store.write().unwrap_or_else(|err| err.into_inner());
+    let status = match store.insert(key, request.body) {
+        Some(_) => Status::NoContent,
+        None => Status::Created,
+    };
+
+    Ok(Response::empty(status))
+}
+
+fn patch(store: &Store, request: Request, params: &Params) -> Result<Response, HttpError> {
+    let mime = request
+        .headers
+        .get_first(&Header::ContentType)
+        .and_then(|content_type| content_type.split(';').next())
+        .map(str::trim);
+
+    if !mime.is_some_and(|mime| mime.eq_ignore_ascii_case(MERGE_PATCH)) {
+        return Err(HttpError::UnsupportedMediaType(format!(
+            "expected {MERGE_PATCH}"
+        )));
+    }
+
+    let patch: Value = serde_json::from_slice(&request.body)
+        .map_err(|err| HttpError::BadRequest(format!("invalid merge patch: {err}")))?;
+
+    let mut store = store.write().unwrap_or_else(|err| err.into_inner());
+    let stored = store.get_mut(key(params)?).ok_or(HttpError::NotFound)?;
+
+    let mut value: Value = serde_json::from_slice(stored)
+        .map_err(|_| HttpError::Conflict("stored value isn't json".to_owned()))?;
+    merge_patch(&mut value, patch);
+
+    *stored = serde_json::to_vec(&value).context("serialize value")?;
+
+    Ok(Response::json(&value).context("serialize value")?)
+}
+
+/// Applies a JSON merge patch to `target`, objects are merged key by key and `null` removes
+/// a key, anything else replaces the target
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+
+    let Value::Object(target) = target else {
+        return;
+    };
+
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+fn delete(store: &Store, params: &Params) -> Result<Response, HttpError> {
+    let mut store = store.write().unwrap_or_else(|err| err.into_inner());
+    store.remove(key(params)?).ok_or(HttpError::NotFound)?;
+
+    Ok(Response::empty(Status::NoContent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_patch() {
+        let mut value = json!({"a": "b", "c": {"d": "e", "f": "g"}});
+        merge_patch(&mut value, json!({"a": "z", "c": {"f": null}}));
+        assert_eq!(value, json!({"a": "z", "c": {"d": "e"}}));
+
+        let mut value = json!({"a": ["b"]});
+        merge_patch(&mut value, json!({"a": "c"}));
+        assert_eq!(value, json!({"a": "c"}));
+
+        let mut value = json!(["a", "b"]);
+        merge_patch(&mut value, json!({"a": {"b": null}}));
+        assert_eq!(value, json!({"a": {}}));
+
+        let mut value = json!({"a": "b"});
+        merge_patch(&mut value, json!(["c"]));
+        assert_eq!(value, json!(["c"]));
+    }
+}
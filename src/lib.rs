@@ -0,0 +1,43 @@
+// Lets `routes!` name this crate the same way from inside it as from outside
+extern crate self as http_server_starter_rust;
+
+pub mod access_log;
+pub mod app;
+pub mod auth;
+pub mod compression;
+pub mod config;
+pub mod cookie;
+pub mod cors;
+pub mod csrf;
+pub mod date;
+pub mod defaults;
+pub mod error;
+pub mod extensions;
+pub mod files;
+pub mod forwarded;
+pub mod header;
+pub mod health;
+#[cfg(feature = "http2")]
+mod http2;
+#[cfg(feature = "kv")]
+pub mod kv;
+pub mod middleware;
+pub mod mime;
+pub mod multipart;
+pub mod negotiate;
+pub mod path;
+pub mod percent;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+pub mod rate_limit;
+pub mod request;
+pub mod request_id;
+pub mod response;
+pub mod router;
+pub mod server;
+pub mod sse;
+pub mod timing;
+pub mod tls;
+#[cfg(feature = "tower")]
+pub mod tower;
+pub mod websocket;
@@ -1,260 +1,259 @@
 use anyhow::Context;
-use nom::{
-    branch::alt,
-    bytes::streaming::{tag, take_until1},
-    combinator::rest,
-    sequence::{separated_pair, tuple},
-    IResult, Parser,
+use clap::Parser;
+use flate2::Compression;
+use http_server_starter_rust::{
+    access_log::AccessLog,
+    app,
+    compression::{Compress, CompressionConfig},
+    config::ServerConfig,
+    csrf::CsrfGuard,
+    files::{self, StaticConfig},
+    forwarded::{Cidr, TrustProxy},
+    health::{self, Metrics},
+    negotiate::Negotiate,
+    server::{self, Server},
+    tls,
 };
 use std::{
-    collections::HashMap,
-    fmt::{self, Display, Formatter},
+    net::{IpAddr, SocketAddr},
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::Arc,
+    thread,
 };
-use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    net::{TcpListener, TcpStream},
-};
-
-#[derive(Debug)]
-pub enum Method {
-    GET,
-    POST,
-    PUT,
-    DELETE,
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Address to listen on, `0.0.0.0` for all IPv4 interfaces or `::` for all interfaces
+    #[arg(long, default_value = "127.0.0.1")]
+    host: IpAddr,
+    /// Port to listen on
+    #[arg(long, default_value_t = 4221, value_parser = clap::value_parser!(u16).range(1..))]
+    port: u16,
+    /// Listen on a Unix socket at this path instead of on `--host` and `--port`
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["host", "port"])]
+    unix_socket: Option<PathBuf>,
+    /// Directory to serve and store files in under `/files/`
+    #[arg(long)]
+    directory: Option<PathBuf>,
+    /// Include dotfiles in the listings of `--directory`
+    #[arg(long)]
+    show_hidden: bool,
+    /// File served for a directory of `--directory` instead of its listing
+    #[arg(long, value_name = "NAME", default_value = "index.html")]
+    index_file: String,
+    /// Forbid directories of `--directory` without an index file instead of listing them
+    #[arg(long)]
+    no_directory_listing: bool,
+    /// PEM certificate chain to serve HTTPS with, requires `--tls-key`
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key for `--tls-cert`
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// File to append an access log in Common Log Format to, `-` for stdout
+    #[arg(long)]
+    access_log: Option<PathBuf>,
+    /// Proxy address or CIDR range whose `X-Forwarded-For` and `X-Real-IP` headers are
+    /// trusted, may be repeated
+    #[arg(long, value_name = "CIDR")]
+    trust_proxy: Vec<Cidr>,
+    /// Name to add to the `Via` header of every response, e.g. `edge-1`
+    #[arg(long, value_parser = parse_server_name)]
+    server_name: Option<String>,
+    /// Worker threads answering connections, the number of CPUs by default
+    #[arg(long)]
+    workers: Option<NonZeroUsize>,
+    /// Connections served at once, more wait to be accepted until one closes
+    #[arg(long, default_value = "1024")]
+    max_connections: NonZeroUsize,
+    /// Don't answer `/robots.txt` and `/favicon.ico` with the built-in defaults
+    #[arg(long)]
+    no_default_routes: bool,
+    /// Leave out the `Server` header, so clients can't tell what software answers them
+    #[arg(long)]
+    no_server_header: bool,
+    /// Refuse requests with malformed header lines instead of skipping those lines
+    #[arg(long)]
+    strict_headers: bool,
+    /// Refuse requests with more header lines than this
+    #[arg(long, default_value_t = 100)]
+    max_header_count: usize,
+    /// Refuse requests with a longer request line than this many bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 8192)]
+    max_request_line_size: usize,
+    /// Refuse requests with a longer header line than this many bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 8192)]
+    max_header_size: usize,
+    /// Say how long each stage of answering took in a `Server-Timing` header
+    #[arg(long)]
+    server_timing: bool,
+    /// Gzip level from 0 for none to 9 for the smallest responses, 1 is the fastest
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(0..=9))]
+    compression_level: u32,
 }
 
-impl Method {
-    pub fn parse(data: &str) -> IResult<&str, Method> {
-        let mut parser = alt((
-            tag("GET").map(|_| Method::GET),
-            tag("POST").map(|_| Method::POST),
-            tag("PUT").map(|_| Method::PUT),
-            tag("DELETE").map(|_| Method::DELETE),
-        ));
-
-        parser(data)
+/// A `Via` pseudonym, which can't contain whitespace or anything else that ends the header
+fn parse_server_name(name: &str) -> Result<String, String> {
+    if !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() && byte != b',')
+    {
+        Ok(name.to_owned())
+    } else {
+        Err("expected visible ASCII characters without commas".to_owned())
     }
 }
 
-#[derive(Debug)]
-pub struct RequestLine {
-    pub method: Method,
-    pub path: String,
-}
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
 
-impl RequestLine {
-    pub fn parse(input: &str) -> IResult<&str, RequestLine> {
-        let space = &tag(" ");
-        let until_space = take_until1(" ");
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
 
-        let mut parser = tuple((Method::parse, space, until_space, space, rest))
-            .map(|(method, _, path, _, _)| {
-                let path = path.to_owned();
+    let workers = args
+        .workers
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
 
-                RequestLine { method, path }
-            })
-            .map(|request_line| RequestLine {
-                method: request_line.method,
-                path: request_line.path,
-            });
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(workers)
+        .enable_all()
+        .build()
+        .context("build runtime")?;
 
-        parser.parse(input)
-    }
-}
+    tracing::info!(workers, "starting runtime");
 
-#[derive(Debug, Eq, PartialEq, Hash)]
-pub enum Header {
-    UserAgent,
-    ContentType,
-    ContentLength,
+    runtime.block_on(run(args))
 }
 
-impl Header {
-    pub fn parse(input: &str) -> IResult<&str, Header> {
-        let mut parser = alt((
-            tag("User-Agent").map(|_| Header::UserAgent),
-            tag("Content-Type").map(|_| Header::ContentType),
-            tag("Content-Length").map(|_| Header::ContentLength),
-        ));
-
-        parser(input)
-    }
-}
+async fn run(args: Args) -> anyhow::Result<()> {
+    let mut config = ServerConfig {
+        trust_proxy: TrustProxy {
+            trusted: args.trust_proxy,
+        },
+        server_name: args.server_name.unwrap_or_default(),
+        max_connections: args.max_connections.get(),
+        serve_defaults: !args.no_default_routes,
+        strict_headers: args.strict_headers,
+        max_header_count: args.max_header_count,
+        max_request_line_bytes: args.max_request_line_size,
+        max_header_line_bytes: args.max_header_size,
+        server_timing: args.server_timing,
+        compression: CompressionConfig {
+            level: Compression::new(args.compression_level),
+            ..CompressionConfig::default()
+        },
+        ..ServerConfig::default()
+    };
 
-impl Display for Header {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self {
-            Header::UserAgent => write!(f, "User-Agent"),
-            Header::ContentType => write!(f, "Content-Type"),
-            Header::ContentLength => write!(f, "Content-Length"),
-        }
+    if args.no_server_header {
+        config.server_header = None;
     }
-}
-
-fn parse_header_value(line: &str) -> IResult<&str, (Header, &str)> {
-    let mut parser = separated_pair(Header::parse, tag(": "), rest);
 
-    parser(line)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+    let mut router = app::router();
 
-    #[test]
-    fn test_parse_method() {
-        let data = "GET / HTTP/1.1\r\n";
-        let result = Method::parse(data);
+    if let Some(directory) = args.directory {
+        if !directory.is_dir() {
+            anyhow::bail!(
+                "--directory {} is not an existing directory",
+                directory.display()
+            );
+        }
 
-        assert!(matches!(result, Ok((_, Method::GET))));
+        router = files::routes(
+            router,
+            StaticConfig {
+                directory_listing: !args.no_directory_listing,
+                index_file: Some(args.index_file),
+                show_hidden: args.show_hidden,
+                ..StaticConfig::new(directory)
+            },
+        );
     }
 
-    #[test]
-    fn test_parse_request_line() {
-        let data = "GET / HTTP/1.1\r\n\r\n";
-        let result = RequestLine::parse(data);
-
-        let (rest, request) = result.expect("parse request");
-
-        // Everything should be consumed
-        assert_eq!(rest, "");
-
-        assert!(matches!(request.method, Method::GET));
-        assert_eq!(request.path, "/".to_owned());
+    #[cfg(feature = "kv")]
+    {
+        router = http_server_starter_rust::kv::routes(router, Default::default());
     }
-}
 
-pub enum Status {
-    Ok,
-    NotFound,
-}
+    let metrics = Arc::new(Metrics::new());
+    router = health::routes(router, metrics.clone());
+
+    router = router.layer(CsrfGuard::default());
+    router = router.layer(Negotiate);
+    router = router.layer(Compress::new(config.compression.clone()));
+
+    // Outermost, so it sees the final status and size
+    if let Some(path) = args.access_log {
+        let access_log = if path.as_os_str() == "-" {
+            AccessLog::stdout()
+        } else {
+            AccessLog::open(&path)
+                .await
+                .with_context(|| format!("open access log {}", path.display()))?
+        };
 
-impl Display for Status {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self {
-            Status::Ok => write!(f, "200 OK"),
-            Status::NotFound => write!(f, "404 Not Found"),
-        }
+        router = router.layer(access_log);
     }
-}
 
-struct Response {
-    status: Status,
-    headers: HashMap<Header, String>,
-    body: String,
-}
-
-impl Display for Response {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "HTTP/1.1 {}\r\n", self.status)?;
+    let mut server = Server::new(router, config).with_metrics(metrics);
 
-        for (header, value) in &self.headers {
-            write!(f, "{}: {}\r\n", header, value)?;
-        }
-
-        write!(f, "\r\n{}", self.body)
+    if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+        server = server.with_tls(tls::acceptor(cert, key)?);
     }
-}
-
-async fn handle_socket(mut stream: TcpStream) -> anyhow::Result<()> {
-    let (reader, mut writer) = stream.split();
 
-    let reader = BufReader::new(reader);
-    let mut lines = reader.lines();
+    #[cfg(unix)]
+    if let Some(path) = args.unix_socket {
+        let listener =
+            server::bind_unix(&path).with_context(|| format!("bind {}", path.display()))?;
 
-    let request_line = lines
-        .next_line()
-        .await
-        .context("read request line")?
-        .context("no request line")?;
+        tracing::info!(path = %path.display(), "listening");
 
-    let mut headers = HashMap::new();
+        let result = server.run(listener, shutdown_signal()).await;
 
-    while let Some(header_line) = lines.next_line().await.context("read header")? {
-        if header_line.is_empty() {
-            break;
+        // Nothing answers on it anymore
+        if let Err(err) = std::fs::remove_file(&path) {
+            tracing::warn!(path = %path.display(), "can't remove socket: {err}");
         }
 
-        let Ok((_, (header, value))) = parse_header_value(&header_line) else {
-            // Unknown header
-            // TODO handle it?
-            continue;
-        };
-
-        headers.insert(header, value.to_owned());
+        return result;
     }
 
-    // TODO parse data
+    let addr = SocketAddr::new(args.host, args.port);
+    let listener = server::bind(addr).with_context(|| format!("bind {addr}"))?;
 
-    let (_, request_line) = RequestLine::parse(&request_line)
-        .map_err(|err| err.to_owned())
-        .context("parse request")?;
-
-    let response = if request_line.path.as_str() == "/" {
-        Response {
-            status: Status::Ok,
-            headers: HashMap::new(),
-            body: String::new(),
-        }
-    } else if request_line.path.as_str() == "/user-agent" {
-        let user_agent = headers
-            .get(&Header::UserAgent)
-            .context("user-agent header not found")?;
-
-        let headers = HashMap::from_iter([
-            (Header::ContentType, "text/plain".to_owned()),
-            (Header::ContentLength, user_agent.len().to_string()),
-        ]);
-
-        let body = user_agent.clone();
-
-        Response {
-            status: Status::Ok,
-            headers,
-            body,
-        }
-    } else if let Some(echo) = request_line.path.strip_prefix("/echo/") {
-        let headers = HashMap::from_iter([
-            (Header::ContentType, "text/plain".to_owned()),
-            (Header::ContentLength, echo.len().to_string()),
-        ]);
-
-        let body = echo.to_owned();
-
-        Response {
-            status: Status::Ok,
-            headers,
-            body,
-        }
-    } else {
-        Response {
-            status: Status::NotFound,
-            headers: HashMap::new(),
-            body: String::new(),
-        }
+    let addr = listener.local_addr().context("local addr")?;
+    let family = match addr.ip() {
+        IpAddr::V4(_) => "IPv4",
+        IpAddr::V6(ip) if ip.is_unspecified() => "IPv6 and IPv4",
+        IpAddr::V6(_) => "IPv6",
     };
 
-    writer
-        .write_all(response.to_string().as_bytes())
-        .await
-        .context("write response")?;
+    tracing::info!(%addr, family, "listening");
 
-    Ok(())
+    server.run(listener, shutdown_signal()).await
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let listener = TcpListener::bind("127.0.0.1:4221")
-        .await
-        .context("bind socket")?;
-
-    loop {
-        let (socket, _) = listener.accept().await.context("accept listener")?;
+/// Resolves on ctrl-c, or SIGTERM on unix
+async fn shutdown_signal() -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
 
-        println!("accepted new connection");
+        let mut terminate = signal(SignalKind::terminate()).context("listen for SIGTERM")?;
 
-        tokio::spawn(handle_socket(socket));
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.context("listen for ctrl-c")?,
+            _ = terminate.recv() => {}
+        }
     }
 
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.context("listen for ctrl-c")?;
+
     Ok(())
 }
@@ -0,0 +1,91 @@
+use crate::{
+    request::Request,
+    response::Response,
+    router::{BoxFuture, Router},
+};
+
+/// Wraps request handling, e.g. to inspect the request or rewrite the response
+///
+/// Implementations can short-circuit by returning a response without calling [`Next::run`].
+/// The request is owned, so it can be modified first, e.g. to add
+/// [`Request::extensions`] for the handler.
+pub trait Middleware: Send + Sync + 'static {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response>;
+}
+
+/// The remaining middleware chain, ending in the routes of the router
+pub struct Next<'a> {
+    middleware: &'a [Box<dyn Middleware>],
+    router: &'a Router,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(middleware: &'a [Box<dyn Middleware>], router: &'a Router) -> Next<'a> {
+        Next { middleware, router }
+    }
+
+    pub fn run(self, request: Request) -> BoxFuture<'a, Response> {
+        match self.middleware.split_first() {
+            Some((middleware, rest)) => middleware.call(request, Next::new(rest, self.router)),
+            None => Box::pin(self.router.dispatch(request)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        header::Header,
+        request::Method,
+        response::{ResponseBody, Status},
+    };
+
+    /// Appends its name to the body
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+            Box::pin(async move {
+                let mut response = next.run(request).await;
+
+                if let ResponseBody::Sized(body) = &mut response.body {
+                    body.extend_from_slice(self.0.as_bytes());
+                }
+
+                response
+            })
+        }
+    }
+
+    /// Refuses requests without a user agent
+    struct RequireUserAgent;
+
+    impl Middleware for RequireUserAgent {
+        fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+            if !request.headers.contains(&Header::UserAgent) {
+                return Box::pin(async { Response::empty(Status::BadRequest) });
+            }
+
+            next.run(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layers() {
+        let router = Router::new()
+            .get("/", |_, _| async { Response::empty(Status::Ok) })
+            .layer(Tag("inner"))
+            .layer(Tag("outer"))
+            .layer(RequireUserAgent);
+
+        let response = router.handle(Request::new(Method::GET, "/")).await;
+        assert_eq!(response.status, Status::BadRequest);
+
+        let mut request = Request::new(Method::GET, "/");
+        request.headers.insert(Header::UserAgent, "test");
+
+        let response = router.handle(request).await;
+        assert_eq!(response.body.as_bytes(), Some(b"innerouter".as_slice()));
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::Path;
+
+/// The content type of files with extension `ext`, `application/octet-stream` if unknown
+pub fn mime_for_extension(ext: &str) -> &'static str {
+    match ext.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "application/javascript",
+        "json" => "application/json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "txt" => "text/plain",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The content type of the file at `path`, based on its extension
+pub fn mime_for_path(path: &Path) -> &'static str {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or("application/octet-stream", mime_for_extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mime_for_path() {
+        assert_eq!(mime_for_path(Path::new("index.html")), "text/html");
+        assert_eq!(mime_for_path(Path::new("photo.JPG")), "image/jpeg");
+        assert_eq!(
+            mime_for_path(Path::new("archive.tar.gz")),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            mime_for_path(Path::new("README")),
+            "application/octet-stream"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::header::{parse_header_value, Header, HeaderMap};
+use anyhow::Context;
+
+/// One field of a `multipart/form-data` body
+#[derive(Debug)]
+pub struct Part {
+    pub name: String,
+    /// Set for file fields, the name of the uploaded file on the client
+    pub filename: Option<String>,
+    pub headers: HeaderMap,
+    pub data: Vec<u8>,
+}
+
+/// The `boundary` parameter of a `multipart/form-data` content type
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';').map(str::trim);
+
+    let mime = params.next()?;
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+
+    params
+        .find_map(|param| param.strip_prefix("boundary="))
+        .map(unquote)
+}
+
+/// Splits `body` into its parts, delimited by `--<boundary>` lines and ended by `--<boundary>--`
+pub fn parse(body: &[u8], boundary: &str) -> anyhow::Result<Vec<Part>> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    // Every delimiter after the first one follows the CRLF ending the previous part
+    let next_delimiter = [b"\r\n", delimiter].concat();
+
+    let start = find(body, delimiter).context("missing first boundary")?;
+    let mut rest = &body[start + delimiter.len()..];
+    let mut parts = Vec::new();
+
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .context("boundary not followed by a line break")?;
+
+        let end = find(rest, &next_delimiter).context("missing closing boundary")?;
+        parts.push(parse_part(&rest[..end])?);
+
+        rest = &rest[end + next_delimiter.len()..];
+    }
+}
+
+/// Parses the headers and data of a single part, separated by an empty line
+fn parse_part(part: &[u8]) -> anyhow::Result<Part> {
+    let headers_end = find(part, b"\r\n\r\n").context("part without headers")?;
+    let head = std::str::from_utf8(&part[..headers_end]).context("part headers aren't utf-8")?;
+
+    let mut headers = HeaderMap::new();
+    for line in head.split("\r\n") {
+        let (_, (header, value)) = parse_header_value(line)
+            .map_err(|_| anyhow::anyhow!("invalid part header {line:?}"))?;
+
+        headers.append(header, value);
+    }
+
+    let disposition = headers
+        .get_first(&Header::ContentDisposition)
+        .context("part without content-disposition")?;
+
+    let param = |name: &str| {
+        disposition
+            .split(';')
+            .map(str::trim)
+            .find_map(|param| param.strip_prefix(name)?.strip_prefix('='))
+            .map(|value| unquote(value).to_owned())
+    };
+
+    let name = param("name").context("part without a name")?;
+    let filename = param("filename");
+
+    Ok(Part {
+        name,
+        filename,
+        headers,
+        data: part[headers_end + 4..].to_vec(),
+    })
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=\"abc123\""),
+            Some("abc123")
+        );
+        assert_eq!(boundary("text/plain; boundary=abc123"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let body = b"preamble\r\n--abc\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            hello\r\n--abc\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            line one\r\nline two\r\n--abc--\r\n";
+
+        let parts = parse(body, "abc").expect("parse multipart");
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "title");
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].data, b"hello");
+        assert_eq!(parts[1].name, "file");
+        assert_eq!(parts[1].filename.as_deref(), Some("a.txt"));
+        assert_eq!(
+            parts[1].headers.get_first(&Header::ContentType),
+            Some("text/plain")
+        );
+        assert_eq!(parts[1].data, b"line one\r\nline two");
+    }
+
+    #[test]
+    fn test_parse_unterminated() {
+        let body = b"--abc\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nhello";
+
+        assert!(parse(body, "abc").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    header::Header,
+    middleware::{Middleware, Next},
+    request::Request,
+    response::{Response, Status},
+    router::BoxFuture,
+};
+
+/// A media range from an `Accept` list item, e.g. `text/*;q=0.5`
+struct MediaRange<'a> {
+    kind: &'a str,
+    subtype: &'a str,
+    quality: f32,
+}
+
+impl<'a> MediaRange<'a> {
+    /// `None` for items that aren't a `type/subtype` or have an invalid quality
+    fn parse(item: &'a str) -> Option<MediaRange<'a>> {
+        let mut params = item.split(';').map(str::trim);
+        let (kind, subtype) = params.next()?.split_once('/')?;
+
+        let quality = match params.find_map(|param| param.strip_prefix("q=")) {
+            Some(quality) => quality.parse().ok().filter(|q| (0.0..=1.0).contains(q))?,
+            None => 1.0,
+        };
+
+        Some(MediaRange {
+            kind,
+            subtype,
+            quality,
+        })
+    }
+
+    /// How specifically the range matches `mime`, higher is more specific
+    fn specificity(&self, kind: &str, subtype: &str) -> Option<u8> {
+        match (self.kind, self.subtype) {
+            ("*", "*") => Some(0),
+            (range, "*") if range.eq_ignore_ascii_case(kind) => Some(1),
+            (range, range_subtype)
+                if range.eq_ignore_ascii_case(kind)
+                    && range_subtype.eq_ignore_ascii_case(subtype) =>
+            {
+                Some(2)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The quality the `Accept` items give `offered`, from the most specific range matching it
+fn quality(ranges: &[MediaRange], offered: &str) -> f32 {
+    let mime = offered.split(';').next().unwrap_or_default().trim();
+    let Some((kind, subtype)) = mime.split_once('/') else {
+        return 0.0;
+    };
+
+    ranges
+        .iter()
+        .filter_map(|range| Some((range.specificity(kind, subtype)?, range.quality)))
+        .max_by_key(|(specificity, _)| *specificity)
+        .map_or(0.0, |(_, quality)| quality)
+}
+
+/// The type in `offered` with the highest quality in the `Accept` list items, earlier wins ties
+///
+/// An empty `accept` accepts anything, so the first type offered is picked.
+pub fn preferred<'a>(accept: &[String], offered: &[&'a str]) -> Option<&'a str> {
+    let ranges: Vec<_> = accept
+        .iter()
+        .filter_map(|item| MediaRange::parse(item))
+        .collect();
+
+    if ranges.is_empty() {
+        return offered.first().copied();
+    }
+
+    let mut best = None;
+    for &offered in offered {
+        let quality = quality(&ranges, offered);
+
+        if quality > 0.0 && best.is_none_or(|(_, best)| quality > best) {
+            best = Some((offered, quality));
+        }
+    }
+
+    best.map(|(offered, _)| offered)
+}
+
+/// Answers 406 Not Acceptable instead of JSON responses the client's `Accept` header refuses
+pub struct Negotiate;
+
+impl Middleware for Negotiate {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Response> {
+        Box::pin(async move {
+            let accepts_json = request
+                .preferred_content_type(&["application/json"])
+                .is_some();
+
+            let mut response = next.run(request).await;
+
+            let is_json = response
+                .headers
+                .get(&Header::ContentType)
+                .and_then(|content_type| content_type.split(';').next())
+                .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+
+            if is_json && !accepts_json {
+                let mut response = Response::empty(Status::NotAcceptable);
+                response.add_vary(Header::Accept);
+
+                return response;
+            }
+
+            if is_json {
+                response.add_vary(Header::Accept);
+            }
+
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::HeaderMap;
+
+    fn preferred_for<'a>(accept: &str, offered: &[&'a str]) -> Option<&'a str> {
+        let mut headers = HeaderMap::new();
+        headers.append(Header::Accept, accept);
+
+        preferred(headers.get_all(&Header::Accept), offered)
+    }
+
+    #[test]
+    fn test_preferred() {
+        let offered = ["application/json", "text/html"];
+
+        assert_eq!(preferred(&[], &offered), Some("application/json"));
+        assert_eq!(preferred_for("text/html", &offered), Some("text/html"));
+        assert_eq!(
+            preferred_for("application/json;q=0.5, text/*", &offered),
+            Some("text/html")
+        );
+        assert_eq!(preferred_for("*/*", &offered), Some("application/json"));
+
+        // The most specific range decides, even if a wildcard has a higher quality
+        assert_eq!(
+            preferred_for("*/*, application/json;q=0", &offered),
+            Some("text/html")
+        );
+        assert_eq!(preferred_for("text/plain", &offered), None);
+    }
+}
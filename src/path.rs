@@ -0,0 +1,81 @@
+use crate::{percent::percent_decode, request::log_parse_error};
+use nom::{
+    bytes::complete::take_till,
+    character::complete::char,
+    combinator::{all_consuming, map_res},
+    multi::many1,
+    sequence::preceded,
+    IResult, Parser,
+};
+
+/// A part of a path between two slashes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// A percent-decoded segment, which may contain an encoded `/`
+    Literal(String),
+    /// Nothing between the slashes, e.g. the end of `/a/` or the middle of `/a//b`
+    Empty,
+}
+
+/// A request path split into its segments, e.g. `/a//b%20c` into `a`, nothing and `b c`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedPath {
+    segments: Vec<Segment>,
+}
+
+impl ParsedPath {
+    /// Parses an absolute path without a query, anything else like `*` is an error
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub fn parse(input: &str) -> IResult<&str, ParsedPath> {
+        let segment = map_res(take_till(|c| c == '/'), |segment: &str| {
+            if segment.is_empty() {
+                Ok(Segment::Empty)
+            } else {
+                percent_decode(segment).map(Segment::Literal)
+            }
+        });
+
+        let mut parser = all_consuming(many1(preceded(char('/'), segment)))
+            .map(|segments| ParsedPath { segments });
+
+        log_parse_error("path", parser.parse(input))
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(segment: &str) -> Segment {
+        Segment::Literal(segment.to_owned())
+    }
+
+    #[test]
+    fn test_parse_path() {
+        let parse = |path| ParsedPath::parse(path).map(|(_, path)| path.segments);
+
+        assert_eq!(parse("/"), Ok(vec![Segment::Empty]));
+        assert_eq!(parse("/a/b"), Ok(vec![literal("a"), literal("b")]));
+        assert_eq!(
+            parse("/a//b/"),
+            Ok(vec![
+                literal("a"),
+                Segment::Empty,
+                literal("b"),
+                Segment::Empty
+            ])
+        );
+        assert_eq!(
+            parse("/hello%20world/a%2Fb"),
+            Ok(vec![literal("hello world"), literal("a/b")])
+        );
+
+        assert!(parse("").is_err());
+        assert!(parse("*").is_err());
+        assert!(parse("/%zz").is_err());
+    }
+}
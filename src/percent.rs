@@ -0,0 +1,92 @@
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("invalid percent sequence at byte {0}")]
+    InvalidSequence(usize),
+
+    #[error("decoded bytes are not valid utf-8")]
+    InvalidUtf8,
+}
+
+/// Decodes `%XX` sequences in `input`
+pub fn percent_decode(input: &str) -> Result<String, DecodeError> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] != b'%' {
+            decoded.push(bytes[index]);
+            index += 1;
+            continue;
+        }
+
+        let byte = input
+            .get(index + 1..index + 3)
+            .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or(DecodeError::InvalidSequence(index))?;
+
+        decoded.push(byte);
+        index += 3;
+    }
+
+    String::from_utf8(decoded).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+/// Encodes every byte of `input` except the unreserved characters from RFC 3986 as `%XX`
+pub fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    encoded
+}
+
+/// Decodes a query string or form component, where `+` also stands for a space
+pub fn form_decode(input: &str) -> Result<String, DecodeError> {
+    percent_decode(&input.replace('+', " "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(
+            percent_decode("hello%20world").as_deref(),
+            Ok("hello world")
+        );
+        assert_eq!(percent_decode("a+b").as_deref(), Ok("a+b"));
+        assert_eq!(percent_decode("%C3%A6").as_deref(), Ok("æ"));
+
+        assert_eq!(percent_decode("%GG"), Err(DecodeError::InvalidSequence(0)));
+        assert_eq!(
+            percent_decode("abc%2"),
+            Err(DecodeError::InvalidSequence(3))
+        );
+        assert_eq!(percent_decode("%FF"), Err(DecodeError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("a b/æ.txt"), "a%20b%2F%C3%A6.txt");
+        assert_eq!(
+            percent_decode(&percent_encode("100% <odd> name")).as_deref(),
+            Ok("100% <odd> name")
+        );
+    }
+
+    #[test]
+    fn test_form_decode() {
+        assert_eq!(form_decode("a+b%2Bc").as_deref(), Ok("a b+c"));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::{
+    header::Header,
+    request::Request,
+    response::{Response, Status, Upgraded},
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{lookup_host, TcpStream},
+    time::timeout,
+};
+
+/// Whether a tunnel may be opened to `ip`, refusing this host and private networks so the
+/// proxy can't be used to reach services that aren't exposed to the client
+fn is_public(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            // Carrier-grade NAT, 100.64.0.0/10
+            let shared = a == 100 && (b & 0xC0) == 64;
+
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || shared)
+        }
+        IpAddr::V6(ip) => {
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local())
+        }
+    }
+}
+
+fn text(status: Status, body: &str) -> Response {
+    Response::builder()
+        .status(status)
+        .header(Header::ContentType, "text/plain")
+        .body(body.to_owned())
+        .build()
+}
+
+/// Answers a `CONNECT host:port` request by connecting to the target, the connection
+/// becomes a tunnel to it once the response is sent
+///
+/// Targets resolving to any non-public address get 400 Bad Request, and targets that can't be
+/// resolved or reached within `connect_timeout` get 502 Bad Gateway.
+pub async fn connect(request: &Request, connect_timeout: Duration) -> Response {
+    let target = request.path.as_str();
+
+    let addrs: Vec<SocketAddr> = match timeout(connect_timeout, lookup_host(target)).await {
+        Ok(Ok(addrs)) => addrs.collect(),
+        Ok(Err(err)) => {
+            tracing::debug!(target, "error resolving tunnel target: {err}");
+            return text(Status::BadGateway, "could not resolve the target");
+        }
+        Err(_) => return text(Status::BadGateway, "timed out resolving the target"),
+    };
+
+    // Checking every address keeps a host with both public and private ones from slipping by
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+        return text(
+            Status::BadRequest,
+            "tunnels to private addresses aren't allowed",
+        );
+    }
+
+    // Connects to the checked addresses, resolving again could give different ones
+    let stream = match timeout(connect_timeout, TcpStream::connect(addrs.as_slice())).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(err)) => {
+            tracing::debug!(target, "error connecting to tunnel target: {err}");
+            return text(Status::BadGateway, "could not connect to the target");
+        }
+        Err(_) => return text(Status::BadGateway, "timed out connecting to the target"),
+    };
+
+    let mut response = Response::empty(Status::ConnectionEstablished);
+    response.upgrade = Some(Box::new(move |upgraded| Box::pin(tunnel(upgraded, stream))));
+
+    response
+}
+
+/// Copies bytes both ways until both sides are done sending
+async fn tunnel(upgraded: Upgraded, target: TcpStream) {
+    let Upgraded {
+        mut reader,
+        mut writer,
+    } = upgraded;
+    let (mut target_reader, mut target_writer) = target.into_split();
+
+    let to_target = async {
+        // The reader may already hold bytes sent right after the request
+        tokio::io::copy_buf(&mut reader, &mut target_writer).await?;
+        target_writer.shutdown().await
+    };
+    let to_client = async {
+        tokio::io::copy(&mut target_reader, &mut writer).await?;
+        writer.shutdown().await
+    };
+
+    if let Err(err) = tokio::try_join!(to_target, to_client) {
+        tracing::debug!("tunnel closed: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, BufReader},
+        net::TcpListener,
+    };
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["93.184.216.34", "2606:2800:220:1::1"] {
+            assert!(is_public(ip.parse().expect("parse ip")), "{ip}");
+        }
+
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().expect("parse ip")), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tunnel() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind listener");
+        let target = TcpStream::connect(listener.local_addr().expect("local addr"))
+            .await
+            .expect("connect");
+        let (mut server, _) = listener.accept().await.expect("accept");
+
+        let (client, proxy_side) = tokio::io::duplex(1024);
+        let (proxy_reader, proxy_writer) = tokio::io::split(proxy_side);
+        let upgraded = Upgraded {
+            reader: Box::new(BufReader::new(proxy_reader)),
+            writer: Box::new(proxy_writer),
+        };
+        let tunnel = tokio::spawn(tunnel(upgraded, target));
+
+        let (mut client_reader, mut client_writer) = tokio::io::split(client);
+        client_writer.write_all(b"ping").await.expect("write");
+        client_writer.shutdown().await.expect("shutdown");
+
+        let mut received = Vec::new();
+        server.read_to_end(&mut received).await.expect("read");
+        assert_eq!(received, b"ping");
+
+        server.write_all(b"pong").await.expect("write");
+        drop(server);
+
+        let mut received = Vec::new();
+        client_reader
+            .read_to_end(&mut received)
+            .await
+            .expect("read");
+        assert_eq!(received, b"pong");
+
+        tunnel.await.expect("tunnel");
+    }
+}
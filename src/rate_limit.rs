@@ -0,0 +1,100 @@
+use crate::config::ServerConfig;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Buckets are pruned once this many addresses are tracked
+const MAX_TRACKED: usize = 10_000;
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Limits the request rate of each remote address with a token bucket
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Tokens a bucket holds when full, i.e. the largest burst allowed
+    capacity: f64,
+    /// Tokens added to a bucket per second
+    refill_rate: f64,
+    buckets: Mutex<HashMap<IpAddr, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u32, refill_rate: f64) -> RateLimiter {
+        RateLimiter {
+            capacity: capacity.into(),
+            refill_rate,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> RateLimiter {
+        RateLimiter::new(config.rate_limit_capacity, config.rate_limit_refill_rate)
+    }
+
+    /// Takes a token for a request from `ip`, or returns how long until one is available
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+
+        if buckets.len() >= MAX_TRACKED {
+            // Full buckets behave the same as untracked ones
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.capacity);
+        }
+
+        let bucket = buckets.entry(ip).or_insert(TokenBucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+
+        let tokens = self.refill(bucket, now);
+
+        if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            bucket.updated = now;
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64((1.0 - tokens) / self.refill_rate))
+    }
+
+    /// The tokens in `bucket` after refilling it up to `now`
+    fn refill(&self, bucket: &TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+
+        (bucket.tokens + elapsed * self.refill_rate).min(self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2, 1.0);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let other = IpAddr::from([127, 0, 0, 2]);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(ip, now).is_ok());
+        assert!(limiter.check_at(ip, now).is_ok());
+        assert_eq!(limiter.check_at(ip, now), Err(Duration::from_secs(1)));
+
+        // Buckets are per address
+        assert!(limiter.check_at(other, now).is_ok());
+
+        let later = now + Duration::from_millis(1500);
+        assert!(limiter.check_at(ip, later).is_ok());
+        assert_eq!(limiter.check_at(ip, later), Err(Duration::from_millis(500)));
+    }
+}
@@ -0,0 +1,1506 @@
+use crate::{
+    config::ServerConfig,
+    cookie,
+    extensions::Extensions,
+    header::{parse_folded_line, parse_header_value, unfold_header_value, Header, HeaderMap},
+    multipart::{self, Part},
+    negotiate,
+    path::{ParsedPath, Segment},
+    percent::{form_decode, DecodeError},
+    response::{Response, Status},
+};
+use anyhow::Context;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use nom::{
+    branch::alt,
+    bytes::{
+        complete::take_till,
+        streaming::{tag, take_until1},
+    },
+    character::{
+        complete::{char, one_of},
+        streaming::satisfy,
+    },
+    combinator::{opt, rest},
+    multi::separated_list0,
+    sequence::{pair, preceded, separated_pair, tuple},
+    IResult, Parser,
+};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    io::{self, Read},
+    net::{IpAddr, SocketAddr},
+};
+use thiserror::Error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    GET,
+    POST,
+    PUT,
+    DELETE,
+    HEAD,
+    OPTIONS,
+    PATCH,
+    TRACE,
+    CONNECT,
+    // WebDAV, which file managers probe servers with
+    PROPFIND,
+    MKCOL,
+    COPY,
+    MOVE,
+}
+
+/// How much of the input a failed parser logs
+const LOGGED_INPUT_LEN: usize = 64;
+
+/// How much of a skipped header line is logged
+const LOGGED_HEADER_LEN: usize = 256;
+
+/// The start of `input`, at most `max_len` bytes long without splitting a character
+fn truncate(input: &str, max_len: usize) -> &str {
+    let end = (0..=input.len().min(max_len))
+        .rev()
+        .find(|&end| input.is_char_boundary(end))
+        .unwrap_or_default();
+
+    &input[..end]
+}
+
+/// Logs where `parser` failed if `result` is an error, so `RUST_LOG=debug` shows what part of
+/// a request was rejected
+pub(crate) fn log_parse_error<'a, T>(
+    parser: &str,
+    result: IResult<&'a str, T>,
+) -> IResult<&'a str, T> {
+    if let Err(nom::Err::Error(err) | nom::Err::Failure(err)) = &result {
+        let input = truncate(err.input, LOGGED_INPUT_LEN);
+
+        tracing::debug!(parser, input, code = ?err.code, "parse failed");
+    }
+
+    result
+}
+
+impl Method {
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub fn parse(data: &str) -> IResult<&str, Method> {
+        let mut parser = alt((
+            tag("GET").map(|_| Method::GET),
+            tag("POST").map(|_| Method::POST),
+            tag("PUT").map(|_| Method::PUT),
+            tag("DELETE").map(|_| Method::DELETE),
+            tag("HEAD").map(|_| Method::HEAD),
+            tag("OPTIONS").map(|_| Method::OPTIONS),
+            tag("PATCH").map(|_| Method::PATCH),
+            tag("TRACE").map(|_| Method::TRACE),
+            tag("CONNECT").map(|_| Method::CONNECT),
+            tag("PROPFIND").map(|_| Method::PROPFIND),
+            tag("MKCOL").map(|_| Method::MKCOL),
+            tag("COPY").map(|_| Method::COPY),
+            tag("MOVE").map(|_| Method::MOVE),
+        ));
+
+        log_parse_error("method", parser(data))
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Method::GET => write!(f, "GET"),
+            Method::POST => write!(f, "POST"),
+            Method::PUT => write!(f, "PUT"),
+            Method::DELETE => write!(f, "DELETE"),
+            Method::HEAD => write!(f, "HEAD"),
+            Method::OPTIONS => write!(f, "OPTIONS"),
+            Method::PATCH => write!(f, "PATCH"),
+            Method::TRACE => write!(f, "TRACE"),
+            Method::CONNECT => write!(f, "CONNECT"),
+            Method::PROPFIND => write!(f, "PROPFIND"),
+            Method::MKCOL => write!(f, "MKCOL"),
+            Method::COPY => write!(f, "COPY"),
+            Method::MOVE => write!(f, "MOVE"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HttpVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl HttpVersion {
+    pub const HTTP_1_0: HttpVersion = HttpVersion { major: 1, minor: 0 };
+    pub const HTTP_1_1: HttpVersion = HttpVersion { major: 1, minor: 1 };
+    pub const HTTP_2: HttpVersion = HttpVersion { major: 2, minor: 0 };
+
+    pub fn parse(input: &str) -> IResult<&str, HttpVersion> {
+        let digit = || satisfy(|char| char.is_ascii_digit()).map(|char| char as u8 - b'0');
+
+        let mut parser = preceded(tag("HTTP/"), separated_pair(digit(), tag("."), digit()))
+            .map(|(major, minor)| HttpVersion { major, minor });
+
+        parser.parse(input)
+    }
+
+    /// Whether connections are kept alive unless the client sends `Connection: close`, as
+    /// opposed to closed unless it sends `Connection: keep-alive`
+    pub fn keeps_alive_by_default(&self) -> bool {
+        *self >= HttpVersion::HTTP_1_1
+    }
+}
+
+impl Display for HttpVersion {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "HTTP/{}.{}", self.major, self.minor)
+    }
+}
+
+#[derive(Debug)]
+pub struct RequestLine {
+    pub method: Method,
+    pub path: String,
+    pub version: HttpVersion,
+}
+
+impl RequestLine {
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub fn parse(input: &str) -> IResult<&str, RequestLine> {
+        let space = &tag(" ");
+        let until_space = take_until1(" ");
+
+        let mut parser = tuple((
+            Method::parse,
+            space,
+            until_space,
+            space,
+            HttpVersion::parse,
+            rest,
+        ))
+        .map(|(method, _, path, _, version, _)| {
+            let path = path.to_owned();
+
+            RequestLine {
+                method,
+                path,
+                version,
+            }
+        })
+        .map(|request_line| RequestLine {
+            method: request_line.method,
+            path: request_line.path,
+            version: request_line.version,
+        });
+
+        log_parse_error("request line", parser.parse(input))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RequestError {
+    #[error("connection closed before request line")]
+    NoRequestLine,
+
+    #[error("invalid request line: {0:?}")]
+    InvalidRequestLine(String),
+
+    #[error("invalid header line: {0:?}")]
+    InvalidHeader(String),
+
+    #[error("request line longer than {0} bytes")]
+    RequestLineTooLong(usize),
+
+    #[error("header line longer than {0} bytes")]
+    HeaderLineTooLong(usize),
+
+    #[error("request line or header line isn't utf-8")]
+    InvalidUtf8Line,
+
+    #[error("more than {0} header lines")]
+    TooManyHeaders(usize),
+
+    #[error("unsupported http version {0}")]
+    UnsupportedVersion(HttpVersion),
+
+    #[error("invalid query string")]
+    InvalidQuery(#[from] DecodeError),
+
+    #[error("invalid content-length: {0:?}")]
+    InvalidContentLength(String),
+
+    #[error("body is shorter than content-length")]
+    Truncated,
+
+    #[error("timed out reading the body")]
+    BodyTimeout,
+
+    #[error("body of {0} bytes exceeds the limit")]
+    BodyTooLarge(usize),
+
+    #[error("invalid chunked body")]
+    InvalidChunk,
+
+    #[error("unsupported transfer encoding: {0:?}")]
+    UnsupportedTransferEncoding(String),
+
+    #[error("unsupported content encoding: {0:?}")]
+    UnsupportedContentEncoding(String),
+
+    #[error("corrupt {0} body")]
+    InvalidContentEncoding(String),
+
+    #[error("missing host header")]
+    MissingHost,
+
+    #[error("invalid host header: {0:?}")]
+    InvalidHost(String),
+
+    #[error("expectation can't be met: {0:?}")]
+    ExpectationFailed(String),
+
+    #[error("read request")]
+    Io(#[from] io::Error),
+}
+
+impl RequestError {
+    /// The status to respond with, or `None` if the connection should just be dropped
+    pub fn status(&self) -> Option<Status> {
+        match self {
+            RequestError::InvalidRequestLine(_)
+            | RequestError::InvalidHeader(_)
+            | RequestError::InvalidQuery(_)
+            | RequestError::InvalidContentLength(_)
+            | RequestError::Truncated
+            | RequestError::InvalidChunk
+            | RequestError::InvalidContentEncoding(_)
+            | RequestError::MissingHost
+            | RequestError::InvalidHost(_)
+            | RequestError::InvalidUtf8Line => Some(Status::BadRequest),
+            RequestError::BodyTimeout => Some(Status::RequestTimeout),
+            RequestError::BodyTooLarge(_) => Some(Status::PayloadTooLarge),
+            RequestError::RequestLineTooLong(_) => Some(Status::UriTooLong),
+            RequestError::HeaderLineTooLong(_) | RequestError::TooManyHeaders(_) => {
+                Some(Status::RequestHeaderFieldsTooLarge)
+            }
+            RequestError::UnsupportedTransferEncoding(_) => Some(Status::NotImplemented),
+            RequestError::UnsupportedContentEncoding(_) => Some(Status::UnsupportedMediaType),
+            RequestError::ExpectationFailed(_) => Some(Status::ExpectationFailed),
+            RequestError::UnsupportedVersion(_) => Some(Status::HttpVersionNotSupported),
+            RequestError::NoRequestLine | RequestError::Io(_) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: Method,
+    /// The path without the query string
+    pub path: String,
+    /// `path` split into segments, `None` if it isn't an absolute path, e.g. `*`
+    segments: Option<ParsedPath>,
+    pub version: HttpVersion,
+    pub query: HashMap<String, String>,
+    /// Every query parameter in the order they were sent, repeated keys included
+    query_params: Vec<(String, String)>,
+    pub headers: HeaderMap,
+    /// The `name=value` pairs of the `Cookie` headers, parsed once when the head is read
+    cookies: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// The peer that sent the request, `None` for requests that didn't come from a TCP socket
+    pub remote_addr: Option<SocketAddr>,
+    /// The client address reported by a trusted proxy, see [`TrustProxy`](crate::forwarded::TrustProxy)
+    pub forwarded_for: Option<IpAddr>,
+    pub extensions: Extensions,
+    /// Header lines that were skipped because they couldn't be parsed
+    pub malformed_header_count: usize,
+}
+
+impl Request {
+    /// A request without headers or body
+    pub fn new(method: Method, path: impl Into<String>) -> Request {
+        let path = path.into();
+
+        Request {
+            method,
+            segments: parsed_path(&path),
+            path,
+            version: HttpVersion::HTTP_1_1,
+            query: HashMap::new(),
+            query_params: Vec::new(),
+            headers: HeaderMap::new(),
+            cookies: Vec::new(),
+            body: Vec::new(),
+            remote_addr: None,
+            forwarded_for: None,
+            extensions: Extensions::new(),
+            malformed_header_count: 0,
+        }
+    }
+
+    /// The address of the client, as reported by a trusted proxy or else the peer's
+    ///
+    /// `None` for requests that didn't come from a TCP socket, e.g. over a Unix socket.
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.forwarded_for
+            .or(self.remote_addr.map(|addr| addr.ip()))
+    }
+
+    /// The percent-decoded segments of the path, empty if it isn't an absolute path or has
+    /// invalid percent sequences
+    pub fn path_segments(&self) -> &[Segment] {
+        self.segments
+            .as_ref()
+            .map(ParsedPath::segments)
+            .unwrap_or_default()
+    }
+
+    /// Checks the `Host` header, which HTTP/1.1 requests must have exactly one of
+    ///
+    /// Hosts that aren't a `host[:port]` could poison caches keyed on them, or end up in
+    /// generated links and headers.
+    pub fn check_host(&self) -> Result<(), RequestError> {
+        let hosts = self.headers.get_all(&Header::Host);
+
+        let host = match hosts {
+            [] if self.version < HttpVersion::HTTP_1_1 => return Ok(()),
+            [] => return Err(RequestError::MissingHost),
+            [host] => host,
+            hosts => return Err(RequestError::InvalidHost(hosts.join(", "))),
+        };
+
+        // `uri-host [ ":" port ]` from RFC 7230, with brackets for IPv6 literals
+        let valid = host
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || "-._~!$&'()*+,;=%:[]".contains(char));
+
+        if !valid {
+            return Err(RequestError::InvalidHost(host.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// The value of the query parameter `name`, the last one if it's repeated
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.get(name).map(String::as_str)
+    }
+
+    /// Every query parameter in the order they were sent, e.g. both values of
+    /// `?tag=a&tag=b`
+    pub fn all_query_params(&self) -> &[(String, String)] {
+        &self.query_params
+    }
+
+    /// The `name=value` cookies sent in `Cookie` headers, in the order they were sent
+    ///
+    /// They're parsed when the request is read, so a request made with [`Request::new`] has
+    /// none. The pairs are owned rather than `(&str, &str)` slices of the `Cookie` header,
+    /// which the request can't store alongside the header they borrow from, see
+    /// [`Request::cookie`] for a borrowed value.
+    pub fn cookies(&self) -> &[(String, String)] {
+        &self.cookies
+    }
+
+    /// The value of the cookie `name`, the first one if it's sent more than once
+    ///
+    /// Browsers send cookies with more specific paths first.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies
+            .iter()
+            .find(|(cookie, _)| cookie == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The type in `offered` the `Accept` header prefers, `None` if it accepts none of them
+    ///
+    /// Without an `Accept` header any type is acceptable, so the first one offered is picked.
+    /// Responses picked this way should [add](Response::add_vary) `Vary: Accept`.
+    pub fn preferred_content_type<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+        negotiate::preferred(self.headers.get_all(&Header::Accept), offered)
+    }
+
+    /// Deserializes the body as JSON
+    pub fn json<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        serde_json::from_slice(&self.body).context("deserialize json body")
+    }
+
+    /// Reads the body announced by the headers of a request from [`Request::parse_head`]
+    pub async fn read_body<R>(
+        &mut self,
+        reader: &mut R,
+        config: &ServerConfig,
+    ) -> Result<(), RequestError>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let body = read_raw_body(reader, &self.headers, config.max_body_bytes).await?;
+        self.set_body(body, config)
+    }
+
+    /// Sets a body that was read in full, decoding its `Content-Encoding`
+    pub(crate) fn set_body(
+        &mut self,
+        body: Vec<u8>,
+        config: &ServerConfig,
+    ) -> Result<(), RequestError> {
+        self.body = decode_content(body, &mut self.headers, config.max_body_bytes)?;
+
+        Ok(())
+    }
+
+    /// Whether the client waits for a `100 Continue` before sending the body
+    ///
+    /// Expectations other than `100-continue` can't be met, and neither can one for a body
+    /// that's announced to be too large, as it would be refused once sent anyway.
+    pub fn expects_continue(&self, config: &ServerConfig) -> Result<bool, RequestError> {
+        let Some(expect) = self.headers.get_first(&Header::Expect) else {
+            return Ok(false);
+        };
+
+        if !expect.eq_ignore_ascii_case("100-continue") {
+            return Err(RequestError::ExpectationFailed(expect.to_owned()));
+        }
+
+        let too_large = self
+            .headers
+            .get_first(&Header::ContentLength)
+            .and_then(parse_content_length)
+            .is_some_and(|length| length > config.max_body_bytes);
+
+        if too_large {
+            return Err(RequestError::ExpectationFailed(expect.to_owned()));
+        }
+
+        // HTTP/1.0 clients don't know interim responses
+        Ok(self.version >= HttpVersion::HTTP_1_1)
+    }
+
+    /// Parses an `application/x-www-form-urlencoded` body
+    pub fn form(&self) -> anyhow::Result<HashMap<String, String>> {
+        let content_type = self.headers.get_first(&Header::ContentType);
+        let mime = content_type
+            .and_then(|content_type| content_type.split(';').next())
+            .map(str::trim);
+
+        if !mime.is_some_and(|mime| mime.eq_ignore_ascii_case("application/x-www-form-urlencoded"))
+        {
+            anyhow::bail!("not an application/x-www-form-urlencoded request");
+        }
+
+        let body = std::str::from_utf8(&self.body).context("form body isn't utf-8")?;
+
+        let pairs = parse_query_string(body).context("decode form body")?;
+
+        Ok(pairs.into_iter().collect())
+    }
+
+    /// Splits a `multipart/form-data` body into its parts
+    pub fn multipart(&self) -> anyhow::Result<Vec<Part>> {
+        let boundary = self
+            .headers
+            .get_first(&Header::ContentType)
+            .and_then(multipart::boundary)
+            .context("not a multipart/form-data request")?;
+
+        multipart::parse(&self.body, boundary)
+    }
+
+    /// Answers a `TRACE` request by echoing its request line and headers back
+    pub fn trace(&self) -> Response {
+        Response::builder()
+            .header(Header::ContentType, "message/http")
+            .body(self.to_string())
+            .build()
+    }
+
+    /// Reads a full request (request line, headers and body) from `reader`
+    pub async fn parse_from_reader<R>(
+        reader: &mut R,
+        config: &ServerConfig,
+    ) -> Result<Request, RequestError>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let mut request = Request::parse_head(reader, config).await?;
+        request.read_body(reader, config).await?;
+
+        Ok(request)
+    }
+
+    /// Reads the request line and headers from `reader`, leaving the body to
+    /// [`Request::read_body`]
+    ///
+    /// Malformed header lines are skipped and counted in
+    /// [`malformed_header_count`](Request::malformed_header_count), and values folded over
+    /// several lines are unfolded, unless [`ServerConfig::strict_headers`] is set.
+    pub async fn parse_head<R>(
+        reader: &mut R,
+        config: &ServerConfig,
+    ) -> Result<Request, RequestError>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let request_line = read_head_line(
+            reader,
+            config.max_request_line_bytes,
+            RequestError::RequestLineTooLong(config.max_request_line_bytes),
+        )
+        .await?
+        .ok_or(RequestError::NoRequestLine)?;
+
+        let (
+            _,
+            RequestLine {
+                method,
+                path,
+                version,
+            },
+        ) = RequestLine::parse(&request_line)
+            .map_err(|_| RequestError::InvalidRequestLine(request_line.clone()))?;
+
+        if version != HttpVersion::HTTP_1_0 && version != HttpVersion::HTTP_1_1 {
+            return Err(RequestError::UnsupportedVersion(version));
+        }
+
+        let mut headers = HeaderMap::new();
+        let mut header_count = 0;
+        let mut malformed_header_count = 0;
+        // The last header and the lines of its value, which may go on over folded lines
+        let mut pending: Option<(Header, Vec<String>)> = None;
+
+        while let Some(header_line) = read_head_line(
+            reader,
+            config.max_header_line_bytes,
+            RequestError::HeaderLineTooLong(config.max_header_line_bytes),
+        )
+        .await?
+        {
+            if header_line.is_empty() {
+                break;
+            }
+
+            // Malformed lines count too, skipping them still takes work
+            header_count += 1;
+            if header_count > config.max_header_count {
+                return Err(RequestError::TooManyHeaders(config.max_header_count));
+            }
+
+            if let (Some((header, lines)), false) = (&mut pending, config.strict_headers) {
+                if let Ok((_, line)) = parse_folded_line(&header_line) {
+                    // Proxies that don't unfold would frame the body differently
+                    if matches!(header, Header::ContentLength | Header::TransferEncoding) {
+                        return Err(RequestError::InvalidHeader(header_line));
+                    }
+
+                    lines.push(line.to_owned());
+                    continue;
+                }
+            }
+
+            if let Some((header, lines)) = pending.take() {
+                let lines: Vec<_> = lines.iter().map(String::as_str).collect();
+                headers.append(header, &unfold_header_value(&lines));
+            }
+
+            let Ok((_, (header, value))) = parse_header_value(&header_line) else {
+                // Proxies may read `Content-Length : 5` as a length, skipping it would let the
+                // body smuggle in another request
+                let space_before_colon = header_line
+                    .split_once(':')
+                    .is_some_and(|(name, _)| name.ends_with([' ', '\t']));
+
+                if config.strict_headers || space_before_colon {
+                    return Err(RequestError::InvalidHeader(header_line));
+                }
+
+                tracing::warn!(
+                    line = truncate(&header_line, LOGGED_HEADER_LEN),
+                    "skipping malformed header"
+                );
+                malformed_header_count += 1;
+                continue;
+            };
+
+            pending = Some((header, vec![value.to_owned()]));
+        }
+
+        if let Some((header, lines)) = pending {
+            let lines: Vec<_> = lines.iter().map(String::as_str).collect();
+            headers.append(header, &unfold_header_value(&lines));
+        }
+
+        let mut request = Request::from_head(method, path, version, headers)?;
+        request.malformed_header_count = malformed_header_count;
+
+        Ok(request)
+    }
+
+    /// A request without a body yet, for the request line and headers of any HTTP version
+    pub(crate) fn from_head(
+        method: Method,
+        target: String,
+        version: HttpVersion,
+        headers: HeaderMap,
+    ) -> Result<Request, RequestError> {
+        let (path, query_params) = match target.split_once('?') {
+            Some((path, query)) => (path.to_owned(), parse_query_string(query)?),
+            None => (target, Vec::new()),
+        };
+
+        Ok(Request {
+            method,
+            segments: parsed_path(&path),
+            path,
+            version,
+            query: query_params.iter().cloned().collect(),
+            query_params,
+            cookies: parse_cookie_headers(&headers),
+            headers,
+            body: Vec::new(),
+            remote_addr: None,
+            forwarded_for: None,
+            extensions: Extensions::new(),
+            malformed_header_count: 0,
+        })
+    }
+}
+
+fn parse_cookie_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .get_all(&Header::Cookie)
+        .iter()
+        .filter_map(|header| cookie::parse_cookies(header).ok())
+        .flat_map(|(_, cookies)| cookies)
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect()
+}
+
+fn parsed_path(path: &str) -> Option<ParsedPath> {
+    ParsedPath::parse(path).ok().map(|(_, path)| path)
+}
+
+/// Formats the request line and headers, the way `TRACE` echoes them
+impl Display for Request {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {} {}\r\n", self.method, self.path, self.version)?;
+
+        for (header, value) in self.headers.iter() {
+            write!(f, "{header}: {value}\r\n")?;
+        }
+
+        write!(f, "\r\n")
+    }
+}
+
+/// `key=value` pairs separated by `&` or `;`, the value is everything after the first `=`
+fn query_pairs(input: &str) -> IResult<&str, Vec<(&str, Option<&str>)>> {
+    let separator = |char| char == '&' || char == ';';
+
+    separated_list0(
+        one_of("&;"),
+        pair(
+            take_till(move |char| char == '=' || separator(char)),
+            opt(preceded(char('='), take_till(separator))),
+        ),
+    )(input)
+}
+
+/// Parses `key=value&key2=value2` into percent-decoded pairs in order, keeping repeated keys
+///
+/// Pairs may also be separated by `;`, and a key without `=` gets an empty value.
+pub fn parse_query_string(query: &str) -> Result<Vec<(String, String)>, DecodeError> {
+    // Every input is some list of pairs, so this can't fail
+    let (_, pairs) = query_pairs(query).unwrap_or_default();
+
+    pairs
+        .into_iter()
+        .filter(|(key, value)| !key.is_empty() || value.is_some())
+        .map(|(key, value)| Ok((form_decode(key)?, form_decode(value.unwrap_or_default())?)))
+        .collect()
+}
+
+/// Longest chunk size or trailer line accepted in a chunked body
+const MAX_CHUNK_LINE: u64 = 4096;
+
+/// Reads the request body following the headers, as described by `Content-Length` or
+/// `Transfer-Encoding`
+async fn read_raw_body<R>(
+    reader: &mut R,
+    headers: &HeaderMap,
+    max_body_bytes: usize,
+) -> Result<Vec<u8>, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let transfer_encodings = headers.get_all(&Header::TransferEncoding);
+
+    if !transfer_encodings.is_empty() {
+        // A length next to a transfer encoding is a request smuggling vector
+        if headers.contains(&Header::ContentLength) {
+            return Err(RequestError::InvalidContentLength(
+                headers.get_all(&Header::ContentLength).join(", "),
+            ));
+        }
+
+        return match transfer_encodings {
+            [encoding] if encoding.eq_ignore_ascii_case("chunked") => {
+                decode_chunked_body(reader, max_body_bytes).await
+            }
+            encodings => Err(RequestError::UnsupportedTransferEncoding(
+                encodings.join(", "),
+            )),
+        };
+    }
+
+    let Some(content_length) = headers.get_first(&Header::ContentLength) else {
+        // No body
+        return Ok(Vec::new());
+    };
+
+    let content_length = parse_content_length(content_length)
+        .ok_or_else(|| RequestError::InvalidContentLength(content_length.to_owned()))?;
+
+    // Conflicting lengths are a request smuggling vector
+    let lengths = headers.get_all(&Header::ContentLength);
+    if lengths
+        .iter()
+        .any(|length| length.trim() != lengths[0].trim())
+    {
+        return Err(RequestError::InvalidContentLength(lengths.join(", ")));
+    }
+
+    if content_length > max_body_bytes {
+        return Err(RequestError::BodyTooLarge(content_length));
+    }
+
+    let mut body = vec![0; content_length];
+
+    reader.read_exact(&mut body).await.map_err(truncated)?;
+
+    Ok(body)
+}
+
+/// Undoes the `Content-Encoding` of `body`, removing the header so handlers see a plain body
+///
+/// The decoded body is held to `max_body_bytes` too, so small compressed bodies can't expand
+/// without bound.
+fn decode_content(
+    body: Vec<u8>,
+    headers: &mut HeaderMap,
+    max_body_bytes: usize,
+) -> Result<Vec<u8>, RequestError> {
+    let encodings = headers.remove(&Header::ContentEncoding);
+    if encodings.is_empty() {
+        return Ok(body);
+    }
+
+    let encodings: Vec<_> = encodings
+        .iter()
+        .flat_map(|encodings| encodings.split(','))
+        .map(str::trim)
+        .filter(|encoding| !encoding.is_empty())
+        .collect();
+
+    let mut body = body;
+
+    // Encodings are listed in the order they were applied
+    for &encoding in encodings.iter().rev() {
+        let decoder: Box<dyn Read> = match encoding.to_ascii_lowercase().as_str() {
+            "identity" => continue,
+            "gzip" | "x-gzip" => Box::new(GzDecoder::new(body.as_slice())),
+            // HTTP's deflate is the zlib format
+            "deflate" => Box::new(ZlibDecoder::new(body.as_slice())),
+            _ => {
+                return Err(RequestError::UnsupportedContentEncoding(
+                    encoding.to_owned(),
+                ))
+            }
+        };
+
+        let mut decoded = Vec::new();
+        decoder
+            .take(max_body_bytes as u64 + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|_| RequestError::InvalidContentEncoding(encoding.to_owned()))?;
+
+        if decoded.len() > max_body_bytes {
+            return Err(RequestError::BodyTooLarge(decoded.len()));
+        }
+
+        body = decoded;
+    }
+
+    if headers.contains(&Header::ContentLength) {
+        headers.insert(Header::ContentLength, body.len().to_string());
+    }
+
+    Ok(body)
+}
+
+/// Reads one line of a request head without its line ending, `None` if the input ends first
+///
+/// Unlike [`AsyncBufReadExt::lines`] it stops with `too_long` as soon as the line is longer
+/// than `max_len` bytes, so a client can't make the server buffer an endless line.
+async fn read_head_line<R>(
+    reader: &mut R,
+    max_len: usize,
+    too_long: RequestError,
+) -> Result<Option<String>, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = Vec::new();
+
+    loop {
+        let buf = reader.fill_buf().await?;
+
+        if buf.is_empty() {
+            if line.is_empty() {
+                return Ok(None);
+            }
+
+            break;
+        }
+
+        match buf.iter().position(|&byte| byte == b'\n') {
+            Some(end) => {
+                line.extend_from_slice(&buf[..end]);
+                reader.consume(end + 1);
+
+                if line.ends_with(b"\r") {
+                    line.pop();
+                }
+
+                break;
+            }
+            None => {
+                let len = buf.len();
+                line.extend_from_slice(buf);
+                reader.consume(len);
+
+                // Leaves room for a `\r` whose `\n` hasn't been read yet
+                if line.len() > max_len + 1 {
+                    return Err(too_long);
+                }
+            }
+        }
+    }
+
+    if line.len() > max_len {
+        return Err(too_long);
+    }
+
+    // A client error like any other malformed line, not a broken connection
+    let line = String::from_utf8(line).map_err(|_| RequestError::InvalidUtf8Line)?;
+
+    Ok(Some(line))
+}
+
+/// Parses a `Content-Length` value, which may only be digits, unlike what `usize` accepts
+fn parse_content_length(length: &str) -> Option<usize> {
+    let length = length.trim();
+
+    if !length.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    length.parse().ok()
+}
+
+/// Reads a `Transfer-Encoding: chunked` body, made of `<hex-size>\r\n<data>\r\n` chunks
+/// ending with an empty chunk and optional trailer fields, which are discarded
+pub async fn decode_chunked_body<R>(
+    reader: &mut R,
+    max_body_bytes: usize,
+) -> Result<Vec<u8>, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut body = Vec::new();
+
+    loop {
+        let line = read_chunk_line(reader).await?;
+
+        // Chunk extensions after `;` aren't used
+        let size = line.split(';').next().unwrap_or_default().trim();
+
+        // `from_str_radix` would also take a sign
+        if !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(RequestError::InvalidChunk);
+        }
+
+        let size = usize::from_str_radix(size, 16).map_err(|_| RequestError::InvalidChunk)?;
+
+        if size == 0 {
+            break;
+        }
+
+        let length = body.len().saturating_add(size);
+        if length > max_body_bytes {
+            return Err(RequestError::BodyTooLarge(length));
+        }
+
+        let start = body.len();
+        body.resize(length, 0);
+        reader
+            .read_exact(&mut body[start..])
+            .await
+            .map_err(truncated)?;
+
+        if !read_chunk_line(reader).await?.is_empty() {
+            return Err(RequestError::InvalidChunk);
+        }
+    }
+
+    // Skip the trailer section up to the final empty line
+    while !read_chunk_line(reader).await?.is_empty() {}
+
+    Ok(body)
+}
+
+/// Reads one CRLF terminated line of a chunked body, without the line ending
+async fn read_chunk_line<R>(reader: &mut R) -> Result<String, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+
+    (&mut *reader)
+        .take(MAX_CHUNK_LINE)
+        .read_line(&mut line)
+        .await?;
+
+    line.strip_suffix("\r\n")
+        .map(str::to_owned)
+        .ok_or(RequestError::InvalidChunk)
+}
+
+/// Maps running out of input in the middle of a body to [`RequestError::Truncated`]
+fn truncated(err: io::Error) -> RequestError {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => RequestError::Truncated,
+        _ => RequestError::Io(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method() {
+        let data = "GET / HTTP/1.1\r\n";
+        let result = Method::parse(data);
+
+        assert!(matches!(result, Ok((_, Method::GET))));
+
+        let result = Method::parse("PATCH /kv/a HTTP/1.1\r\n");
+        assert!(matches!(result, Ok((_, Method::PATCH))));
+
+        let result = Method::parse("CONNECT example.com:443 HTTP/1.1\r\n");
+        assert!(matches!(result, Ok((_, Method::CONNECT))));
+
+        let result = Method::parse("PROPFIND / HTTP/1.1\r\n");
+        assert!(matches!(result, Ok((_, Method::PROPFIND))));
+
+        let result = Method::parse("MOVE /a HTTP/1.1\r\n");
+        assert!(matches!(result, Ok((_, Method::MOVE))));
+    }
+
+    #[test]
+    fn test_form() {
+        let mut request = Request::new(Method::POST, "/login");
+        request.headers.append(
+            Header::ContentType,
+            "application/x-www-form-urlencoded; charset=utf-8",
+        );
+        request.body = b"user=J%C3%B8rn+Doe&remember&next=%2Fhome".to_vec();
+
+        let form = request.form().expect("parse form");
+        assert_eq!(form.get("user").map(String::as_str), Some("Jørn Doe"));
+        assert_eq!(form.get("remember").map(String::as_str), Some(""));
+        assert_eq!(form.get("next").map(String::as_str), Some("/home"));
+
+        request.body = b"user=%FF".to_vec();
+        assert!(request.form().is_err());
+
+        let request = Request::new(Method::POST, "/login");
+        assert!(request.form().is_err());
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut request = Request::new(Method::TRACE, "/debug");
+        request.headers.append(Header::UserAgent, "curl/8.0");
+
+        let response = request.trace();
+
+        assert_eq!(
+            response.headers.get(&Header::ContentType),
+            Some(&"message/http".to_owned())
+        );
+        assert_eq!(
+            response.body.as_bytes(),
+            Some(b"TRACE /debug HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n".as_slice())
+        );
+    }
+
+    #[test]
+    fn test_parse_request_line() {
+        let data = "GET / HTTP/1.1\r\n\r\n";
+        let result = RequestLine::parse(data);
+
+        let (rest, request) = result.expect("parse request");
+
+        // Everything should be consumed
+        assert_eq!(rest, "");
+
+        assert!(matches!(request.method, Method::GET));
+        assert_eq!(request.path, "/".to_owned());
+        assert_eq!(request.version, HttpVersion::HTTP_1_1);
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_version() {
+        let mut data = "GET / HTTP/1.0\r\n\r\n".as_bytes();
+        let request = Request::parse_from_reader(&mut data, &ServerConfig::default())
+            .await
+            .expect("parse request");
+
+        assert_eq!(request.version, HttpVersion::HTTP_1_0);
+        assert!(!request.version.keeps_alive_by_default());
+
+        for line in ["GET / HTTP/0.9\r\n\r\n", "GET / HTTP/2.0\r\n\r\n"] {
+            let mut data = line.as_bytes();
+            let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+
+            assert!(matches!(result, Err(RequestError::UnsupportedVersion(_))));
+        }
+
+        let mut data = "GET / FTP/1.1\r\n\r\n".as_bytes();
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+        assert!(matches!(result, Err(RequestError::InvalidRequestLine(_))));
+
+        let mut data = "GET / HTTP/1.1\r\nContent-Length : 5\r\n\r\nhello".as_bytes();
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+        assert!(matches!(result, Err(RequestError::InvalidHeader(_))));
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_malformed_header() {
+        let data = "GET / HTTP/1.1\r\nHost: localhost\r\nBad Header: yes\r\nno colon\r\n\r\n";
+
+        let request = Request::parse_from_reader(&mut data.as_bytes(), &ServerConfig::default())
+            .await
+            .expect("parse request");
+        assert_eq!(request.malformed_header_count, 2);
+        assert_eq!(request.headers.get_first(&Header::Host), Some("localhost"));
+
+        let config = ServerConfig {
+            strict_headers: true,
+            ..ServerConfig::default()
+        };
+        let result = Request::parse_from_reader(&mut data.as_bytes(), &config).await;
+        assert!(matches!(result, Err(RequestError::InvalidHeader(_))));
+    }
+
+    #[tokio::test]
+    async fn test_read_head_line() {
+        let read = |input: &'static str| async move {
+            // A small buffer so long lines take several reads
+            let mut reader = tokio::io::BufReader::with_capacity(4, input.as_bytes());
+            read_head_line(&mut reader, 8, RequestError::HeaderLineTooLong(8)).await
+        };
+
+        assert!(matches!(read("12345678\r\n").await, Ok(Some(line)) if line == "12345678"));
+        assert!(matches!(read("12345678\n").await, Ok(Some(line)) if line == "12345678"));
+        assert!(matches!(read("1234").await, Ok(Some(line)) if line == "1234"));
+        assert!(matches!(read("").await, Ok(None)));
+        // obs-text is allowed in header values, but only utf-8 is understood
+        let mut not_utf8 = &b"X-A: \xff\r\n"[..];
+        assert!(matches!(
+            read_head_line(&mut not_utf8, 8192, RequestError::HeaderLineTooLong(8192)).await,
+            Err(RequestError::InvalidUtf8Line)
+        ));
+        assert!(matches!(
+            read("123456789\r\n").await,
+            Err(RequestError::HeaderLineTooLong(8))
+        ));
+        // Gives up before the end of the line arrives
+        assert!(matches!(
+            read("12345678901234567890").await,
+            Err(RequestError::HeaderLineTooLong(8))
+        ));
+
+        let mut not_utf8 = &b"GET / HTTP/1.1\r\nX-Name: caf\xe9\r\n\r\n"[..];
+        let result = Request::parse_from_reader(&mut not_utf8, &ServerConfig::default()).await;
+        assert_eq!(result.unwrap_err().status(), Some(Status::BadRequest));
+
+        let long_target = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(8192));
+        let result =
+            Request::parse_from_reader(&mut long_target.as_bytes(), &ServerConfig::default()).await;
+        assert_eq!(result.unwrap_err().status(), Some(Status::UriTooLong));
+
+        let long_header = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(8192));
+        let result =
+            Request::parse_from_reader(&mut long_header.as_bytes(), &ServerConfig::default()).await;
+        assert_eq!(
+            result.unwrap_err().status(),
+            Some(Status::RequestHeaderFieldsTooLarge)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_folded_header() {
+        let data = "GET / HTTP/1.1\r\nHost: localhost\r\nX-Folded: a\r\n  b\r\n\tc \r\n\
+            Accept: text/html,\r\n text/plain\r\n\r\n";
+
+        let request = Request::parse_from_reader(&mut data.as_bytes(), &ServerConfig::default())
+            .await
+            .expect("parse request");
+        assert_eq!(
+            request
+                .headers
+                .get_first(&Header::Custom("x-folded".to_owned())),
+            Some("a b c")
+        );
+        assert_eq!(
+            request.headers.get_all(&Header::Accept),
+            ["text/html", "text/plain"]
+        );
+        assert_eq!(request.malformed_header_count, 0);
+
+        let config = ServerConfig {
+            strict_headers: true,
+            ..ServerConfig::default()
+        };
+        let result = Request::parse_from_reader(&mut data.as_bytes(), &config).await;
+        assert!(matches!(result, Err(RequestError::InvalidHeader(_))));
+
+        // Nothing to continue, so it's malformed
+        let data = "GET / HTTP/1.1\r\n folded\r\nHost: localhost\r\n\r\n";
+        let request = Request::parse_from_reader(&mut data.as_bytes(), &ServerConfig::default())
+            .await
+            .expect("parse request");
+        assert_eq!(request.malformed_header_count, 1);
+
+        let data = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length:\r\n 5\r\n\r\nhello";
+        let result =
+            Request::parse_from_reader(&mut data.as_bytes(), &ServerConfig::default()).await;
+        assert!(matches!(result, Err(RequestError::InvalidHeader(_))));
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_too_many_headers() {
+        let headers = |count| {
+            let headers: String = (0..count)
+                .map(|i| format!("X-Header-{i}: {i}\r\n"))
+                .collect();
+            format!("GET / HTTP/1.1\r\n{headers}\r\n")
+        };
+        let config = ServerConfig::default();
+
+        let data = headers(100);
+        let request = Request::parse_from_reader(&mut data.as_bytes(), &config).await;
+        assert!(request.is_ok());
+
+        let data = headers(101);
+        let result = Request::parse_from_reader(&mut data.as_bytes(), &config).await;
+        assert!(matches!(result, Err(RequestError::TooManyHeaders(100))));
+        assert_eq!(
+            result.unwrap_err().status(),
+            Some(Status::RequestHeaderFieldsTooLarge)
+        );
+    }
+
+    #[test]
+    fn test_check_host() {
+        let mut request = Request::new(Method::GET, "/");
+        assert!(matches!(
+            request.check_host(),
+            Err(RequestError::MissingHost)
+        ));
+
+        request.headers.append(Header::Host, "[::1]:4221");
+        assert!(request.check_host().is_ok());
+
+        request
+            .headers
+            .insert(Header::Host, "evil.com\0.example.com");
+        assert!(matches!(
+            request.check_host(),
+            Err(RequestError::InvalidHost(_))
+        ));
+
+        request.headers.insert(Header::Host, "a.com");
+        request.headers.append(Header::Host, "b.com");
+        assert!(matches!(
+            request.check_host(),
+            Err(RequestError::InvalidHost(_))
+        ));
+
+        // HTTP/1.0 predates the header
+        let mut request = Request::new(Method::GET, "/");
+        request.version = HttpVersion::HTTP_1_0;
+        assert!(request.check_host().is_ok());
+    }
+
+    #[test]
+    fn test_log_parse_error() {
+        // Cut on a char boundary, 64 bytes falls inside an `æ`
+        let input = format!("x{}", "æ".repeat(64));
+        let result = log_parse_error("method", Method::parse(&input));
+
+        assert!(matches!(result, Err(nom::Err::Error(_))));
+    }
+
+    #[test]
+    fn test_expects_continue() {
+        let config = ServerConfig::default();
+
+        let mut request = Request::new(Method::POST, "/");
+        assert!(matches!(request.expects_continue(&config), Ok(false)));
+
+        request.headers.append(Header::Expect, "100-Continue");
+        request.headers.append(Header::ContentLength, "5");
+        assert!(matches!(request.expects_continue(&config), Ok(true)));
+
+        request.headers.insert(Header::ContentLength, "10000000000");
+        assert!(matches!(
+            request.expects_continue(&config),
+            Err(RequestError::ExpectationFailed(_))
+        ));
+
+        let mut request = Request::new(Method::POST, "/");
+        request.headers.append(Header::Expect, "something-else");
+        assert!(matches!(
+            request.expects_continue(&config),
+            Err(RequestError::ExpectationFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_parse_request() {
+        let mut data = "POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello".as_bytes();
+
+        let request = Request::parse_from_reader(&mut data, &ServerConfig::default())
+            .await
+            .expect("parse request");
+
+        assert!(matches!(request.method, Method::POST));
+        assert_eq!(request.path, "/echo");
+        assert_eq!(request.headers.get_first(&Header::ContentLength), Some("5"));
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_query() {
+        let mut data = "GET /search?q=hello+world&page=2&empty HTTP/1.1\r\n\r\n".as_bytes();
+
+        let request = Request::parse_from_reader(&mut data, &ServerConfig::default())
+            .await
+            .expect("parse request");
+
+        assert_eq!(request.path, "/search");
+        assert_eq!(request.query_param("q"), Some("hello world"));
+        assert_eq!(request.query_param("page"), Some("2"));
+        assert_eq!(request.query_param("empty"), Some(""));
+        assert_eq!(request.query_param("missing"), None);
+    }
+
+    #[tokio::test]
+    async fn test_cookies() {
+        let mut data = "GET / HTTP/1.1\r\n\
+            Cookie: session=abc; theme=\"dark mode\"\r\n\
+            Cookie: session=old\r\n\r\n"
+            .as_bytes();
+
+        let request = Request::parse_from_reader(&mut data, &ServerConfig::default())
+            .await
+            .expect("parse request");
+
+        let cookies: Vec<_> = request
+            .cookies()
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            cookies,
+            [
+                ("session", "abc"),
+                ("theme", "dark mode"),
+                ("session", "old")
+            ]
+        );
+        assert_eq!(request.cookie("session"), Some("abc"));
+        assert_eq!(request.cookie("theme"), Some("dark mode"));
+        assert_eq!(request.cookie("missing"), None);
+
+        assert!(Request::new(Method::GET, "/").cookies().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_repeated_query() {
+        let mut data = "GET /posts?tag=a&tag=b;page=2 HTTP/1.1\r\n\r\n".as_bytes();
+
+        let request = Request::parse_from_reader(&mut data, &ServerConfig::default())
+            .await
+            .expect("parse request");
+
+        let pairs: Vec<_> = request
+            .all_query_params()
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(pairs, [("tag", "a"), ("tag", "b"), ("page", "2")]);
+        assert_eq!(request.query_param("tag"), Some("b"));
+    }
+
+    #[test]
+    fn test_parse_query_string() {
+        let pairs = |query| parse_query_string(query).expect("parse query");
+        let owned = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+
+        assert_eq!(pairs(""), owned(&[]));
+        assert_eq!(pairs("a=1&&b"), owned(&[("a", "1"), ("b", "")]));
+        assert_eq!(
+            pairs("tag=a;tag=b&x=y=z"),
+            owned(&[("tag", "a"), ("tag", "b"), ("x", "y=z")])
+        );
+        assert_eq!(
+            pairs("q=hello+world&name=te%65vik"),
+            owned(&[("q", "hello world"), ("name", "teevik")])
+        );
+        assert_eq!(pairs("=x"), owned(&[("", "x")]));
+
+        assert!(parse_query_string("a=%zz").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_truncated_body() {
+        let mut data = "POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello".as_bytes();
+
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+
+        assert!(matches!(result, Err(RequestError::Truncated)));
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_body_too_large() {
+        let mut data = "POST / HTTP/1.1\r\nContent-Length: 10000000000\r\n\r\n".as_bytes();
+
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+
+        assert!(matches!(result, Err(RequestError::BodyTooLarge(_))));
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_invalid_content_length() {
+        for length in ["+5", "-5", "5x", ""] {
+            let data = format!("POST / HTTP/1.1\r\nContent-Length: {length}\r\n\r\nhello");
+
+            let result =
+                Request::parse_from_reader(&mut data.as_bytes(), &ServerConfig::default()).await;
+
+            assert!(
+                matches!(result, Err(RequestError::InvalidContentLength(_))),
+                "{length:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_chunked() {
+        let mut data =
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nExpires: never\r\n\r\n"
+                .as_bytes();
+
+        let request = Request::parse_from_reader(&mut data, &ServerConfig::default())
+            .await
+            .expect("parse request");
+
+        assert_eq!(request.body, b"hello world");
+        assert!(data.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_invalid_chunk() {
+        let mut data =
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nhello\r\n0\r\n\r\n"
+                .as_bytes();
+
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+        assert!(matches!(result, Err(RequestError::InvalidChunk)));
+
+        let mut data =
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nhello\r\n0\r\n\r\n"
+                .as_bytes();
+
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+        assert!(matches!(result, Err(RequestError::InvalidChunk)));
+
+        let mut data =
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n+5\r\nhello\r\n0\r\n\r\n"
+                .as_bytes();
+
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+        assert!(matches!(result, Err(RequestError::InvalidChunk)));
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_unsupported_transfer_encoding() {
+        let mut data = "POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n".as_bytes();
+
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+
+        assert!(matches!(
+            result,
+            Err(RequestError::UnsupportedTransferEncoding(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_parse_request_content_encoding() {
+        let body =
+            crate::compression::gzip(b"hello", flate2::Compression::default()).expect("gzip body");
+        let head = format!(
+            "POST / HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        let data = [head.as_bytes(), &body].concat();
+
+        let request = Request::parse_from_reader(&mut data.as_slice(), &ServerConfig::default())
+            .await
+            .expect("parse request");
+
+        assert_eq!(request.body, b"hello");
+        assert!(!request.headers.contains(&Header::ContentEncoding));
+
+        let mut data =
+            "POST / HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: 5\r\n\r\nhello"
+                .as_bytes();
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+        assert!(matches!(
+            result,
+            Err(RequestError::InvalidContentEncoding(_))
+        ));
+
+        let mut data =
+            "POST / HTTP/1.1\r\nContent-Encoding: br\r\nContent-Length: 5\r\n\r\nhello".as_bytes();
+        let result = Request::parse_from_reader(&mut data, &ServerConfig::default()).await;
+        assert!(matches!(
+            result,
+            Err(RequestError::UnsupportedContentEncoding(_))
+        ));
+    }
+}
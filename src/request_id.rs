@@ -0,0 +1,75 @@
+use crate::header::{Header, HeaderMap};
+use std::fmt::{self, Display, Formatter};
+use uuid::Uuid;
+
+/// Longest incoming `X-Request-Id` that is reused, anything longer gets a fresh ID
+const MAX_LEN: usize = 200;
+
+/// Identifies a request in the logs and to the client through the `X-Request-Id` header
+///
+/// Available to handlers through [`Request::extensions`](crate::request::Request::extensions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// A random v4 UUID
+    pub fn new() -> RequestId {
+        RequestId(Uuid::new_v4().to_string())
+    }
+
+    /// The `X-Request-Id` the request came with, if it's short visible ASCII that is safe to
+    /// log and echo back
+    pub fn from_headers(headers: &HeaderMap) -> Option<RequestId> {
+        let id = headers.get_first(&Header::RequestId)?.trim();
+
+        let is_valid =
+            !id.is_empty() && id.len() <= MAX_LEN && id.bytes().all(|byte| byte.is_ascii_graphic());
+
+        is_valid.then(|| RequestId(id.to_owned()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> RequestId {
+        RequestId::new()
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id() {
+        let id = RequestId::new();
+        assert_eq!(id.as_str().len(), 36);
+        assert_ne!(id, RequestId::new());
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(RequestId::from_headers(&headers), None);
+
+        headers.append(Header::RequestId, "abc-123");
+        assert_eq!(
+            RequestId::from_headers(&headers),
+            Some(RequestId("abc-123".to_owned()))
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.append(Header::RequestId, "has spaces");
+        assert_eq!(RequestId::from_headers(&headers), None);
+
+        let mut headers = HeaderMap::new();
+        headers.append(Header::RequestId, &"a".repeat(MAX_LEN + 1));
+        assert_eq!(RequestId::from_headers(&headers), None);
+    }
+}
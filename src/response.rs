@@ -0,0 +1,920 @@
+use crate::{
+    cookie::{self, CookieOptions},
+    date::format_http_date,
+    header::{Header, ResponseHeaders},
+    percent::percent_encode,
+    request::HttpVersion,
+    router::BoxFuture,
+};
+use anyhow::Context;
+use nom::{
+    branch::alt,
+    bytes::complete::take_while_m_n,
+    character::complete::char,
+    combinator::{eof, map_res, rest},
+    sequence::{preceded, tuple},
+    IResult, Parser,
+};
+use serde::Serialize;
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{self, Write},
+    time::{Duration, SystemTime},
+};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Size of the buffer chunked bodies are streamed through, and so the largest chunk sent
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Longest streamed body buffered for an HTTP/1.0 client to send its length, longer ones are
+/// sent without a length
+const HTTP_1_0_BUFFER_LIMIT: usize = 8 * 1024 * 1024;
+
+/// Room reserved for the status line and headers, enough for most responses
+const HEAD_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Continue,
+    SwitchingProtocols,
+    Ok,
+    /// `200` answering a `CONNECT`, the connection is a tunnel afterwards so there's no body
+    ConnectionEstablished,
+    Created,
+    NoContent,
+    PartialContent,
+    MovedPermanently,
+    Found,
+    NotModified,
+    TemporaryRedirect,
+    PermanentRedirect,
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    NotAcceptable,
+    RequestTimeout,
+    Conflict,
+    PayloadTooLarge,
+    UriTooLong,
+    UnsupportedMediaType,
+    RangeNotSatisfiable,
+    ExpectationFailed,
+    TooManyRequests,
+    RequestHeaderFieldsTooLarge,
+    InternalServerError,
+    NotImplemented,
+    BadGateway,
+    HttpVersionNotSupported,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Status::Continue => write!(f, "100 Continue"),
+            Status::SwitchingProtocols => write!(f, "101 Switching Protocols"),
+            Status::Ok => write!(f, "200 OK"),
+            Status::ConnectionEstablished => write!(f, "200 Connection Established"),
+            Status::Created => write!(f, "201 Created"),
+            Status::NoContent => write!(f, "204 No Content"),
+            Status::PartialContent => write!(f, "206 Partial Content"),
+            Status::MovedPermanently => write!(f, "301 Moved Permanently"),
+            Status::Found => write!(f, "302 Found"),
+            Status::NotModified => write!(f, "304 Not Modified"),
+            Status::TemporaryRedirect => write!(f, "307 Temporary Redirect"),
+            Status::PermanentRedirect => write!(f, "308 Permanent Redirect"),
+            Status::BadRequest => write!(f, "400 Bad Request"),
+            Status::Unauthorized => write!(f, "401 Unauthorized"),
+            Status::Forbidden => write!(f, "403 Forbidden"),
+            Status::NotFound => write!(f, "404 Not Found"),
+            Status::MethodNotAllowed => write!(f, "405 Method Not Allowed"),
+            Status::NotAcceptable => write!(f, "406 Not Acceptable"),
+            Status::RequestTimeout => write!(f, "408 Request Timeout"),
+            Status::Conflict => write!(f, "409 Conflict"),
+            Status::PayloadTooLarge => write!(f, "413 Payload Too Large"),
+            Status::UriTooLong => write!(f, "414 URI Too Long"),
+            Status::UnsupportedMediaType => write!(f, "415 Unsupported Media Type"),
+            Status::RangeNotSatisfiable => write!(f, "416 Range Not Satisfiable"),
+            Status::ExpectationFailed => write!(f, "417 Expectation Failed"),
+            Status::TooManyRequests => write!(f, "429 Too Many Requests"),
+            Status::RequestHeaderFieldsTooLarge => {
+                write!(f, "431 Request Header Fields Too Large")
+            }
+            Status::InternalServerError => write!(f, "500 Internal Server Error"),
+            Status::NotImplemented => write!(f, "501 Not Implemented"),
+            Status::BadGateway => write!(f, "502 Bad Gateway"),
+            Status::HttpVersionNotSupported => write!(f, "505 HTTP Version Not Supported"),
+        }
+    }
+}
+
+impl Status {
+    pub fn code(&self) -> u16 {
+        match self {
+            Status::Continue => 100,
+            Status::SwitchingProtocols => 101,
+            Status::Ok | Status::ConnectionEstablished => 200,
+            Status::Created => 201,
+            Status::NoContent => 204,
+            Status::PartialContent => 206,
+            Status::MovedPermanently => 301,
+            Status::Found => 302,
+            Status::NotModified => 304,
+            Status::TemporaryRedirect => 307,
+            Status::PermanentRedirect => 308,
+            Status::BadRequest => 400,
+            Status::Unauthorized => 401,
+            Status::Forbidden => 403,
+            Status::NotFound => 404,
+            Status::MethodNotAllowed => 405,
+            Status::NotAcceptable => 406,
+            Status::RequestTimeout => 408,
+            Status::Conflict => 409,
+            Status::PayloadTooLarge => 413,
+            Status::UriTooLong => 414,
+            Status::UnsupportedMediaType => 415,
+            Status::RangeNotSatisfiable => 416,
+            Status::ExpectationFailed => 417,
+            Status::TooManyRequests => 429,
+            Status::RequestHeaderFieldsTooLarge => 431,
+            Status::InternalServerError => 500,
+            Status::NotImplemented => 501,
+            Status::BadGateway => 502,
+            Status::HttpVersionNotSupported => 505,
+        }
+    }
+
+    /// Whether the status sends the client to the `Location` header
+    pub fn is_redirect(&self) -> bool {
+        matches!(
+            self,
+            Status::MovedPermanently
+                | Status::Found
+                | Status::TemporaryRedirect
+                | Status::PermanentRedirect
+        )
+    }
+
+    /// Whether a response with this status may carry a body, and so needs its length sent
+    pub fn has_body(&self) -> bool {
+        !matches!(
+            self,
+            Status::Continue
+                | Status::SwitchingProtocols
+                | Status::ConnectionEstablished
+                | Status::NoContent
+                | Status::NotModified
+        )
+    }
+}
+
+pub enum ResponseBody {
+    /// A body of known length, sent with `Content-Length`
+    Sized(Vec<u8>),
+    /// A body of unknown length, sent with `Transfer-Encoding: chunked`
+    Chunked(Box<dyn AsyncRead + Send + Unpin>),
+}
+
+impl ResponseBody {
+    /// The bytes of a sized body, `None` if it's chunked
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            ResponseBody::Sized(bytes) => Some(bytes),
+            ResponseBody::Chunked(_) => None,
+        }
+    }
+}
+
+impl From<Vec<u8>> for ResponseBody {
+    fn from(bytes: Vec<u8>) -> Self {
+        ResponseBody::Sized(bytes)
+    }
+}
+
+/// A connection handed over by the server once the response that upgraded it was sent
+pub struct Upgraded {
+    /// May still hold bytes the client sent right after the request
+    pub reader: Box<dyn AsyncBufRead + Send + Unpin>,
+    pub writer: Box<dyn AsyncWrite + Send + Unpin>,
+}
+
+/// The first line of a response, like `HTTP/1.1 404 Not Found`, the counterpart of
+/// [`RequestLine`](crate::request::RequestLine) for reading back what a server sent
+#[derive(Debug, PartialEq, Eq)]
+pub struct ResponseLine {
+    pub version: HttpVersion,
+    pub status_code: u16,
+    /// May be empty, clients aren't supposed to rely on it
+    pub reason: String,
+}
+
+impl ResponseLine {
+    pub fn parse(input: &str) -> IResult<&str, ResponseLine> {
+        let status_code = map_res(
+            take_while_m_n(3, 3, |char: char| char.is_ascii_digit()),
+            str::parse,
+        );
+        let reason = alt((preceded(char(' '), rest), eof));
+
+        let mut parser = tuple((HttpVersion::parse, char(' '), status_code, reason)).map(
+            |(version, _, status_code, reason)| ResponseLine {
+                version,
+                status_code,
+                reason: reason.to_owned(),
+            },
+        );
+
+        parser.parse(input)
+    }
+}
+
+/// Takes over a connection once the response carrying it has been sent
+pub type OnUpgrade = Box<dyn FnOnce(Upgraded) -> BoxFuture<'static, ()> + Send>;
+
+pub struct Response {
+    pub status: Status,
+    pub headers: ResponseHeaders,
+    pub body: ResponseBody,
+    /// Whether the body may be compressed if the client supports it
+    pub compress: bool,
+    /// Takes over the connection after a `101 Switching Protocols` response, or a tunnel
+    /// opened by `CONNECT`
+    pub upgrade: Option<OnUpgrade>,
+}
+
+impl Response {
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    /// A response with no headers and an empty body
+    pub fn empty(status: Status) -> Response {
+        Response::builder().status(status).build()
+    }
+
+    /// Sends the client to `location`, an absolute or relative URI, with a redirect `status`
+    ///
+    /// An empty `location`, one with control characters that could inject headers, or a status
+    /// that isn't a redirect is a bug in the handler and gets 500 Internal Server Error.
+    pub fn redirect(location: &str, status: Status) -> Response {
+        if location.is_empty() || location.chars().any(char::is_control) {
+            tracing::error!("invalid redirect location {location:?}");
+            return Response::empty(Status::InternalServerError);
+        }
+
+        if !status.is_redirect() {
+            tracing::error!("redirect with non-redirect status {status}");
+            return Response::empty(Status::InternalServerError);
+        }
+
+        Response::builder()
+            .status(status)
+            .header(Header::Location, location)
+            .build()
+    }
+
+    /// Appends `1.1 <server_name>` to `Via`, after any intermediaries already listed
+    pub fn add_via(&mut self, server_name: &str) {
+        let via = format!("1.1 {server_name}");
+
+        match self.headers.get_mut(&Header::Via) {
+            Some(existing) => {
+                existing.push_str(", ");
+                existing.push_str(&via);
+            }
+            None => {
+                self.headers.insert(Header::Via, via);
+            }
+        }
+    }
+
+    /// Adds `header` to `Vary`, telling caches the response depends on that request header
+    pub fn add_vary(&mut self, header: Header) {
+        let name = header.to_string();
+
+        match self.headers.get_mut(&Header::Vary) {
+            Some(vary) => {
+                let listed = vary
+                    .split(',')
+                    .map(str::trim)
+                    .any(|item| item == "*" || item.eq_ignore_ascii_case(&name));
+
+                if !listed {
+                    vary.push_str(", ");
+                    vary.push_str(&name);
+                }
+            }
+            None => {
+                self.headers.insert(Header::Vary, name);
+            }
+        }
+    }
+
+    /// A `200 OK` response with `value` serialized as a JSON body
+    pub fn json(value: &impl Serialize) -> anyhow::Result<Response> {
+        let body = serde_json::to_vec(value).context("serialize json body")?;
+
+        let response = Response::builder()
+            .header(Header::ContentType, "application/json")
+            .body(body)
+            .build();
+
+        Ok(response)
+    }
+
+    /// Appends the status line, headers and a sized body to `buf`, so a reused buffer saves
+    /// allocating for every response
+    ///
+    /// A chunked body can only be streamed, so just the status line and headers are appended.
+    /// Neither is a body given to a status that can't have one, e.g. 204 No Content.
+    pub fn serialize_into(&self, buf: &mut Vec<u8>) {
+        self.serialize_head_into(buf, false);
+
+        if let ResponseBody::Sized(body) = &self.body {
+            if self.status.has_body() {
+                buf.extend_from_slice(body);
+            }
+        }
+    }
+
+    /// Appends the status line and headers to `buf`, without the length or transfer encoding
+    /// if the body ends when the connection closes
+    fn serialize_head_into(&self, buf: &mut Vec<u8>, close_delimited: bool) {
+        let chunked = matches!(self.body, ResponseBody::Chunked(_));
+        // Neither a response without a body nor one ending with the connection says how long
+        // the body is
+        let unframed = close_delimited || !self.status.has_body();
+
+        // Writing to a `Vec` can't fail
+        let _ = write!(buf, "HTTP/1.1 {}\r\n", self.status);
+
+        for (header, value) in &self.headers {
+            // The length of a chunked body isn't known up front, and unframed ones have none
+            if (chunked || unframed)
+                && matches!(header, Header::ContentLength | Header::TransferEncoding)
+            {
+                continue;
+            }
+
+            let _ = write!(buf, "{header}: ");
+            buf.extend_from_slice(value.as_bytes());
+            buf.extend_from_slice(b"\r\n");
+        }
+
+        match &self.body {
+            _ if unframed => {}
+            ResponseBody::Chunked(_) => {
+                let _ = write!(buf, "{}: chunked\r\n", Header::TransferEncoding);
+            }
+            // Without a length a kept-alive client can't tell where the body ends
+            ResponseBody::Sized(body) if !self.headers.contains_key(&Header::ContentLength) => {
+                let _ = write!(buf, "{}: {}\r\n", Header::ContentLength, body.len());
+            }
+            ResponseBody::Sized(_) => {}
+        }
+
+        buf.extend_from_slice(b"\r\n");
+    }
+
+    /// Writes only the status line and headers to `writer`, as the response to a HEAD request
+    pub async fn write_head_to<W>(self, writer: &mut W) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut buf = Vec::with_capacity(HEAD_CAPACITY);
+        self.serialize_head_into(&mut buf, false);
+
+        writer.write_all(&buf).await
+    }
+
+    /// Writes the status line, headers and body to `writer`
+    pub async fn write_to<W>(self, writer: &mut W) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let body_len = self.body.as_bytes().map_or(0, <[u8]>::len);
+        let mut buf = Vec::with_capacity(HEAD_CAPACITY + body_len);
+
+        // A sized body goes out in the same write as the head
+        self.serialize_into(&mut buf);
+        writer.write_all(&buf).await?;
+
+        if let ResponseBody::Chunked(mut reader) = self.body {
+            if self.status.has_body() {
+                write_chunked(&mut reader, writer).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the response for an HTTP/1.0 client, which doesn't understand chunked bodies,
+    /// only the status line and headers if `head` is set
+    ///
+    /// A streamed body is read in full to send its length. One longer than 8 MiB is sent as
+    /// it's read without a length instead, which the client reads until the connection
+    /// closes, so the connection must be closed afterwards.
+    pub async fn write_http_1_0_to<W>(self, writer: &mut W, head: bool) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.write_unchunked_to(writer, head, HTTP_1_0_BUFFER_LIMIT)
+            .await
+    }
+
+    async fn write_unchunked_to<W>(
+        mut self,
+        writer: &mut W,
+        head: bool,
+        limit: usize,
+    ) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut reader = match std::mem::replace(&mut self.body, ResponseBody::Sized(Vec::new())) {
+            ResponseBody::Chunked(reader) => reader,
+            body => {
+                self.body = body;
+
+                return if head {
+                    self.write_head_to(writer).await
+                } else {
+                    self.write_to(writer).await
+                };
+            }
+        };
+
+        let mut buf = Vec::with_capacity(HEAD_CAPACITY);
+
+        // Finding out the length would mean reading a body that isn't sent
+        if head || !self.status.has_body() {
+            self.serialize_head_into(&mut buf, true);
+            return writer.write_all(&buf).await;
+        }
+
+        let mut body = Vec::new();
+        (&mut reader)
+            .take(limit as u64 + 1)
+            .read_to_end(&mut body)
+            .await?;
+
+        if body.len() <= limit {
+            self.body = ResponseBody::Sized(body);
+            return self.write_to(writer).await;
+        }
+
+        self.serialize_head_into(&mut buf, true);
+        buf.extend_from_slice(&body);
+        writer.write_all(&buf).await?;
+
+        tokio::io::copy(&mut reader, writer).await?;
+        writer.flush().await
+    }
+}
+
+/// A `Content-Disposition` value, with `filename*` from RFC 6266 for names that aren't plain
+/// ASCII, since old clients only understand the quoted `filename`
+fn content_disposition(disposition: &str, filename: Option<&str>) -> String {
+    let Some(filename) = filename else {
+        return disposition.to_owned();
+    };
+
+    let mut quoted = String::with_capacity(filename.len());
+    for char in filename.chars() {
+        match char {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(char);
+            }
+            // Stand-ins for what can't be put in a quoted string
+            char if !char.is_ascii() || char.is_ascii_control() => quoted.push('_'),
+            char => quoted.push(char),
+        }
+    }
+
+    let mut value = format!("{disposition}; filename=\"{quoted}\"");
+
+    if !filename
+        .bytes()
+        .all(|byte| byte.is_ascii_graphic() || byte == b' ')
+    {
+        value.push_str("; filename*=UTF-8''");
+        value.push_str(&percent_encode(filename));
+    }
+
+    value
+}
+
+/// What a handler may return, see [`HttpError`](crate::error::HttpError) for returning errors
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+/// Builds a [`Response`], defaulting to `200 OK` with an empty body
+pub struct ResponseBuilder {
+    status: Status,
+    headers: ResponseHeaders,
+    body: ResponseBody,
+    compress: bool,
+}
+
+impl ResponseBuilder {
+    pub fn new() -> ResponseBuilder {
+        ResponseBuilder {
+            status: Status::Ok,
+            headers: ResponseHeaders::new(),
+            body: ResponseBody::Sized(Vec::new()),
+            compress: false,
+        }
+    }
+
+    pub fn status(mut self, status: Status) -> ResponseBuilder {
+        self.status = status;
+        self
+    }
+
+    /// Sets `header`, replacing any previous value
+    pub fn header(mut self, header: Header, value: impl Into<String>) -> ResponseBuilder {
+        self.headers.insert(header, value.into());
+        self
+    }
+
+    /// Lets clients and caches reuse the response for `duration`
+    pub fn cache_for(self, duration: Duration) -> ResponseBuilder {
+        let expires = SystemTime::now() + duration;
+
+        self.header(
+            Header::CacheControl,
+            format!("max-age={}", duration.as_secs()),
+        )
+        .header(Header::Expires, format_http_date(expires))
+    }
+
+    /// Stops clients and caches from storing the response
+    pub fn no_cache(self) -> ResponseBuilder {
+        self.header(Header::CacheControl, "no-store")
+    }
+
+    /// Makes browsers download the body as `filename` instead of displaying it
+    pub fn attachment(self, filename: &str) -> ResponseBuilder {
+        self.header(
+            Header::ContentDisposition,
+            content_disposition("attachment", Some(filename)),
+        )
+    }
+
+    /// Makes browsers display the body, and save it as `filename` if the user asks to
+    pub fn inline(self, filename: Option<&str>) -> ResponseBuilder {
+        self.header(
+            Header::ContentDisposition,
+            content_disposition("inline", filename),
+        )
+    }
+
+    /// Adds another `header` line, keeping any earlier values, for headers sent once per
+    /// value like `Link`
+    pub fn append_header(mut self, header: Header, value: impl Into<String>) -> ResponseBuilder {
+        self.headers.append(header, value);
+        self
+    }
+
+    /// Sets a cookie on the client, in a `Set-Cookie` header of its own next to any other
+    /// cookies set
+    pub fn set_cookie(self, name: &str, value: &str, options: CookieOptions) -> ResponseBuilder {
+        self.append_header(Header::SetCookie, cookie::set_cookie(name, value, &options))
+    }
+
+    /// Sets a sized body, and `Content-Length` unless it's already set
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> ResponseBuilder {
+        let body = body.into();
+
+        self.headers
+            .get_or_insert_with(Header::ContentLength, || body.len().to_string());
+
+        self.body = ResponseBody::Sized(body);
+        self
+    }
+
+    /// Streams the body from `reader` with `Transfer-Encoding: chunked`
+    pub fn chunked_body(
+        mut self,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+    ) -> ResponseBuilder {
+        self.body = ResponseBody::Chunked(Box::new(reader));
+        self
+    }
+
+    /// Allows the body to be compressed if the client supports it
+    pub fn compress(mut self) -> ResponseBuilder {
+        self.compress = true;
+        self
+    }
+
+    pub fn build(self) -> Response {
+        Response {
+            status: self.status,
+            headers: self.headers,
+            body: self.body,
+            compress: self.compress,
+            upgrade: None,
+        }
+    }
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        ResponseBuilder::new()
+    }
+}
+
+/// Copies `reader` to `writer` framed as `<hex-len>\r\n<data>\r\n`, ending with an empty chunk
+async fn write_chunked<R, W>(reader: &mut R, writer: &mut W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+
+        if read == 0 {
+            writer.write_all(b"0\r\n\r\n").await?;
+            return Ok(());
+        }
+
+        writer.write_all(format!("{read:x}\r\n").as_bytes()).await?;
+        writer.write_all(&buffer[..read]).await?;
+        writer.write_all(b"\r\n").await?;
+        // Streamed bodies like event streams should reach the client as they're produced
+        writer.flush().await?;
+    }
+}
+
+/// Formats the status line and headers, the body is written separately by [`Response::write_to`]
+impl Display for Response {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut buf = Vec::with_capacity(HEAD_CAPACITY);
+        self.serialize_head_into(&mut buf, false);
+
+        // Headers are built from strings, so this is always utf-8
+        f.write_str(&String::from_utf8_lossy(&buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_content_length() {
+        let response = Response::builder()
+            .status(Status::Created)
+            .header(Header::ContentType, "text/plain")
+            .body("hello")
+            .build();
+
+        assert_eq!(response.status, Status::Created);
+        assert_eq!(
+            response.headers.get(&Header::ContentLength),
+            Some(&"5".to_owned())
+        );
+        assert_eq!(response.body.as_bytes(), Some(b"hello".as_slice()));
+    }
+
+    #[test]
+    fn test_parse_response_line() {
+        let (_, line) = ResponseLine::parse("HTTP/1.1 404 Not Found").expect("parse line");
+        assert_eq!(
+            line,
+            ResponseLine {
+                version: HttpVersion::HTTP_1_1,
+                status_code: 404,
+                reason: "Not Found".to_owned(),
+            }
+        );
+
+        let (_, line) = ResponseLine::parse("HTTP/1.0 204").expect("parse line");
+        assert_eq!(line.status_code, 204);
+        assert_eq!(line.reason, "");
+
+        // Whatever the server writes can be read back
+        let head = Response::empty(Status::PermanentRedirect).to_string();
+        let (_, line) =
+            ResponseLine::parse(head.lines().next().unwrap_or_default()).expect("parse line");
+        assert_eq!(line.status_code, Status::PermanentRedirect.code());
+        assert_eq!(line.reason, "Permanent Redirect");
+
+        assert!(ResponseLine::parse("HTTP/1.1 20 OK").is_err());
+        assert!(ResponseLine::parse("HTTP/1.1 2000 OK").is_err());
+        assert!(ResponseLine::parse("ICY 200 OK").is_err());
+    }
+
+    #[test]
+    fn test_serialize_into() {
+        let response = Response::builder()
+            .status(Status::Created)
+            .body("hi")
+            .build();
+
+        let mut buf = b"leftover ".to_vec();
+        response.serialize_into(&mut buf);
+        assert_eq!(
+            buf,
+            b"leftover HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nhi".as_slice()
+        );
+
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_multiple_set_cookie() {
+        let response = Response::builder()
+            .set_cookie("session", "abc123", CookieOptions::default())
+            .set_cookie("theme", "dark", CookieOptions::default())
+            .append_header(Header::Custom("Link".to_owned()), "</a.css>; rel=preload")
+            .append_header(Header::Custom("Link".to_owned()), "</b.js>; rel=preload")
+            .body("hi")
+            .build();
+
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\n\
+             Set-Cookie: session=abc123\r\n\
+             Set-Cookie: theme=dark\r\n\
+             Link: </a.css>; rel=preload\r\n\
+             Link: </b.js>; rel=preload\r\n\
+             Content-Length: 2\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_redirect() {
+        let response = Response::redirect("/new?page=2", Status::Found);
+        assert_eq!(response.status, Status::Found);
+        assert_eq!(
+            response.headers.get(&Header::Location),
+            Some(&"/new?page=2".to_owned())
+        );
+
+        let response = Response::redirect("/new\r\nSet-Cookie: a=b", Status::Found);
+        assert_eq!(response.status, Status::InternalServerError);
+
+        let response = Response::redirect("", Status::Found);
+        assert_eq!(response.status, Status::InternalServerError);
+
+        let response = Response::redirect("/new", Status::Ok);
+        assert_eq!(response.status, Status::InternalServerError);
+    }
+
+    #[test]
+    fn test_add_vary() {
+        let mut response = Response::empty(Status::Ok);
+
+        response.add_vary(Header::Accept);
+        response.add_vary(Header::AcceptEncoding);
+        response.add_vary(Header::Accept);
+
+        assert_eq!(
+            response.headers.get(&Header::Vary),
+            Some(&"Accept, Accept-Encoding".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_add_via() {
+        let mut response = Response::empty(Status::Ok);
+
+        response.add_via("edge");
+        assert_eq!(
+            response.headers.get(&Header::Via),
+            Some(&"1.1 edge".to_owned())
+        );
+
+        response.add_via("origin");
+        assert_eq!(
+            response.headers.get(&Header::Via),
+            Some(&"1.1 edge, 1.1 origin".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        let disposition =
+            |response: Response| response.headers[&Header::ContentDisposition].clone();
+
+        let response = Response::builder().attachment("report 2024.pdf").build();
+        assert_eq!(
+            disposition(response),
+            "attachment; filename=\"report 2024.pdf\""
+        );
+
+        let response = Response::builder().attachment("say \"hi\".txt").build();
+        assert_eq!(
+            disposition(response),
+            "attachment; filename=\"say \\\"hi\\\".txt\""
+        );
+
+        let response = Response::builder().attachment("blåbær.txt").build();
+        assert_eq!(
+            disposition(response),
+            "attachment; filename=\"bl_b_r.txt\"; filename*=UTF-8''bl%C3%A5b%C3%A6r.txt"
+        );
+
+        let response = Response::builder().inline(None).build();
+        assert_eq!(disposition(response), "inline");
+
+        let response = Response::builder().inline(Some("photo.jpg")).build();
+        assert_eq!(disposition(response), "inline; filename=\"photo.jpg\"");
+    }
+
+    #[test]
+    fn test_cache_for() {
+        let response = Response::builder()
+            .cache_for(Duration::from_secs(3600))
+            .build();
+
+        assert_eq!(
+            response.headers.get(&Header::CacheControl),
+            Some(&"max-age=3600".to_owned())
+        );
+        assert!(response
+            .headers
+            .get(&Header::Expires)
+            .is_some_and(|expires| expires.ends_with(" GMT")));
+    }
+
+    #[tokio::test]
+    async fn test_write_chunked() {
+        let response = Response::builder()
+            .chunked_body("hello world".as_bytes())
+            .build();
+
+        let mut output = Vec::new();
+        response
+            .write_to(&mut output)
+            .await
+            .expect("write response");
+
+        assert_eq!(
+            String::from_utf8(output).expect("utf-8"),
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nb\r\nhello world\r\n0\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_no_content() {
+        // A body or length given anyway isn't sent
+        let response = Response::builder()
+            .status(Status::NoContent)
+            .header(Header::ContentLength, "5")
+            .body("hello")
+            .build();
+
+        let mut output = Vec::new();
+        response
+            .write_to(&mut output)
+            .await
+            .expect("write response");
+
+        assert_eq!(
+            String::from_utf8(output).expect("utf-8"),
+            "HTTP/1.1 204 No Content\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_unchunked() {
+        let write = |head, limit| async move {
+            let response = Response::builder()
+                .chunked_body("hello world".as_bytes())
+                .build();
+
+            let mut output = Vec::new();
+            response
+                .write_unchunked_to(&mut output, head, limit)
+                .await
+                .expect("write response");
+
+            String::from_utf8(output).expect("utf-8")
+        };
+
+        assert_eq!(
+            write(false, 11).await,
+            "HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello world"
+        );
+        // Too long to buffer, so it ends when the connection closes
+        assert_eq!(write(false, 4).await, "HTTP/1.1 200 OK\r\n\r\nhello world");
+        assert_eq!(write(true, 11).await, "HTTP/1.1 200 OK\r\n\r\n");
+    }
+}
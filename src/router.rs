@@ -0,0 +1,591 @@
+use crate::{
+    cors::CorsConfig,
+    error::HttpError,
+    header::Header,
+    middleware::{Middleware, Next},
+    path::Segment,
+    request::{Method, Request},
+    response::{IntoResponse, Response, Status},
+    websocket::{self, WebSocketConn},
+};
+use itertools::Itertools;
+use std::{collections::HashMap, future::Future, iter, pin::Pin, sync::Arc};
+
+pub use http_server_starter_rust_macros::routes;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Path parameters captured by `:name` segments of a route pattern
+#[derive(Debug, Default)]
+pub struct Params(HashMap<String, String>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+/// Answers the requests of a route, for handlers that are easier to write as a type than as
+/// a closure, e.g. ones holding a database connection
+///
+/// Closures taking a [`Request`] and [`Params`] are handlers too, as long as their future
+/// resolves to something [`IntoResponse`].
+pub trait Handler: Send + Sync + 'static {
+    fn call<'a>(
+        &'a self,
+        request: Request,
+        params: Params,
+    ) -> BoxFuture<'a, Result<Response, HttpError>>;
+}
+
+impl<F, Fut> Handler for F
+where
+    F: Fn(Request, Params) -> Fut + Send + Sync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoResponse,
+{
+    fn call<'a>(
+        &'a self,
+        request: Request,
+        params: Params,
+    ) -> BoxFuture<'a, Result<Response, HttpError>> {
+        let response = self(request, params);
+        Box::pin(async move { Ok(response.await.into_response()) })
+    }
+}
+
+pub struct Route {
+    method: Method,
+    pattern: String,
+    handler: Box<dyn Handler>,
+}
+
+impl Route {
+    /// Whether the route answers `method`
+    fn allows(&self, method: Method) -> bool {
+        // HEAD is answered by the GET handler, the body is dropped when writing
+        let head_of_get = method == Method::HEAD && self.method == Method::GET;
+
+        self.method == method || head_of_get
+    }
+
+    /// Matches the path `segments` against the pattern, capturing `:name` segments
+    ///
+    /// Empty segments only match empty pattern segments, so `/a//b` isn't `/a/b`.
+    fn matches(&self, segments: &[Segment]) -> Option<Params> {
+        // Patterns start with a slash, like paths
+        let mut pattern_segments = self.pattern.split('/').skip(1);
+        let mut path_segments = segments.iter();
+        let mut params = HashMap::new();
+
+        loop {
+            match (pattern_segments.next(), path_segments.next()) {
+                (None, None) => return Some(Params(params)),
+                (Some(pattern), Some(Segment::Literal(segment))) => {
+                    if let Some(name) = pattern.strip_prefix(':') {
+                        params.insert(name.to_owned(), segment.clone());
+                    } else if pattern != segment {
+                        return None;
+                    }
+                }
+                (Some(""), Some(Segment::Empty)) => {}
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Dispatches requests to the first route matching their method and path
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    /// Outermost first
+    middleware: Vec<Box<dyn Middleware>>,
+    not_found: Option<Box<dyn Handler>>,
+    method_not_allowed: Option<Box<dyn Handler>>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Wraps everything registered so far, making `middleware` the outermost layer
+    pub fn layer(mut self, middleware: impl Middleware) -> Router {
+        self.middleware.insert(0, Box::new(middleware));
+        self
+    }
+
+    /// Answers CORS preflight requests and adds CORS headers to every response
+    pub fn with_cors(self, cors: CorsConfig) -> Router {
+        self.layer(cors)
+    }
+
+    /// Adds a route, answered by a [`Handler`]
+    pub fn route(mut self, method: Method, pattern: &str, handler: impl Handler) -> Router {
+        self.routes.push(Route {
+            method,
+            pattern: pattern.to_owned(),
+            handler: Box::new(handler),
+        });
+
+        self
+    }
+
+    /// Answers requests no route matches, instead of the built-in plain text
+    /// `404 Not Found`
+    pub fn not_found(mut self, handler: impl Handler) -> Router {
+        self.not_found = Some(Box::new(handler));
+        self
+    }
+
+    /// Answers requests for paths that only have routes for other methods, instead of the
+    /// built-in plain text `405 Method Not Allowed`
+    ///
+    /// The methods the path does have are listed in `Allow` unless `handler` sets it.
+    pub fn method_not_allowed(mut self, handler: impl Handler) -> Router {
+        self.method_not_allowed = Some(Box::new(handler));
+        self
+    }
+
+    pub fn get(self, pattern: &str, handler: impl Handler) -> Router {
+        self.route(Method::GET, pattern, handler)
+    }
+
+    /// Permanently redirects `GET` requests for `from` to `to`
+    pub fn redirect(self, from: &str, to: &str) -> Router {
+        let to = to.to_owned();
+
+        self.get(from, move |_, _| {
+            let response = Response::redirect(&to, Status::PermanentRedirect);
+            async move { response }
+        })
+    }
+
+    /// Accepts WebSocket connections on `pattern`, `handler` takes over once the handshake is done
+    pub fn websocket<F, Fut>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(WebSocketConn, Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+
+        self.get(pattern, move |request, params| {
+            let handler = handler.clone();
+
+            async move { websocket::upgrade(&request, move |conn| handler(conn, params)) }
+        })
+    }
+
+    pub fn post(self, pattern: &str, handler: impl Handler) -> Router {
+        self.route(Method::POST, pattern, handler)
+    }
+
+    pub fn put(self, pattern: &str, handler: impl Handler) -> Router {
+        self.route(Method::PUT, pattern, handler)
+    }
+
+    pub fn delete(self, pattern: &str, handler: impl Handler) -> Router {
+        self.route(Method::DELETE, pattern, handler)
+    }
+
+    pub fn patch(self, pattern: &str, handler: impl Handler) -> Router {
+        self.route(Method::PATCH, pattern, handler)
+    }
+
+    pub async fn handle(&self, request: Request) -> Response {
+        Next::new(&self.middleware, self).run(request).await
+    }
+
+    /// The `Allow` value for the path of `request`, the methods its routes answer
+    pub(crate) fn allowed(&self, request: &Request) -> String {
+        let segments = request.path_segments();
+
+        let methods = self
+            .routes
+            .iter()
+            .filter(|route| route.matches(segments).is_some())
+            .map(|route| route.method);
+
+        allow(methods)
+    }
+
+    /// Calls the handler of the first matching route, skipping middleware
+    ///
+    /// A path that only has routes for other methods gets 405 Method Not Allowed, with the
+    /// methods it does have in `Allow`. Either is answered by the handler set for it, if any.
+    pub(crate) async fn dispatch(&self, request: Request) -> Response {
+        // Asks about the server as a whole rather than any path
+        if request.method == Method::OPTIONS && request.path == "*" {
+            let methods = self.routes.iter().map(|route| route.method);
+
+            return Response::builder()
+                .header(Header::Allow, allow(methods.chain([Method::OPTIONS])))
+                .build();
+        }
+
+        // Without routes of their own, WebDAV clients learn right away there's no WebDAV here
+        let webdav = matches!(
+            request.method,
+            Method::PROPFIND | Method::MKCOL | Method::COPY | Method::MOVE
+        );
+
+        if webdav
+            && !self
+                .routes
+                .iter()
+                .any(|route| route.method == request.method)
+        {
+            return Response::builder()
+                .status(Status::NotImplemented)
+                .header(Header::ContentType, "text/plain")
+                .body("WebDAV is not supported")
+                .build();
+        }
+
+        let segments = request.path_segments();
+
+        // Not a path, or one with invalid percent sequences
+        if segments.is_empty() {
+            return Response::empty(Status::BadRequest);
+        }
+
+        let mut allowed = Vec::new();
+
+        for route in &self.routes {
+            let Some(params) = route.matches(segments) else {
+                continue;
+            };
+
+            if route.allows(request.method) {
+                return route.handler.call(request, params).await.into_response();
+            }
+
+            allowed.push(route.method);
+        }
+
+        if allowed.is_empty() {
+            return match &self.not_found {
+                Some(handler) => handler
+                    .call(request, Params::default())
+                    .await
+                    .into_response(),
+                None => plain_text(Status::NotFound, "Not Found\n"),
+            };
+        }
+
+        let mut response = match &self.method_not_allowed {
+            Some(handler) => handler
+                .call(request, Params::default())
+                .await
+                .into_response(),
+            None => plain_text(Status::MethodNotAllowed, "Method Not Allowed\n"),
+        };
+
+        response
+            .headers
+            .get_or_insert_with(Header::Allow, || allow(allowed.into_iter()));
+
+        response
+    }
+}
+
+/// The built-in answer when no route handles a request
+fn plain_text(status: Status, body: &str) -> Response {
+    Response::builder()
+        .status(status)
+        .header(Header::ContentType, "text/plain")
+        .body(body)
+        .build()
+}
+
+/// An `Allow` value listing `methods` once each, with `HEAD` wherever `GET` is answered
+fn allow(methods: impl Iterator<Item = Method>) -> String {
+    methods
+        .flat_map(|method| {
+            let head = (method == Method::GET).then_some(Method::HEAD);
+            iter::once(method).chain(head)
+        })
+        .unique()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, path: &str) -> Request {
+        Request::new(method, path)
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let router = Router::new()
+            .get("/", |_, _| async { Response::empty(Status::Ok) })
+            .post("/", |_, _| async { Response::empty(Status::BadRequest) })
+            .get("/echo/:text", |_, _| async {
+                Response::empty(Status::NotImplemented)
+            });
+
+        let response = router.handle(request(Method::GET, "/")).await;
+        assert!(matches!(response.status, Status::Ok));
+
+        let response = router.handle(request(Method::POST, "/")).await;
+        assert!(matches!(response.status, Status::BadRequest));
+
+        let response = router.handle(request(Method::GET, "/echo/abc")).await;
+        assert!(matches!(response.status, Status::NotImplemented));
+
+        let response = router.handle(request(Method::GET, "/missing")).await;
+        assert!(matches!(response.status, Status::NotFound));
+
+        let response = router.handle(request(Method::HEAD, "/")).await;
+        assert!(matches!(response.status, Status::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_routes_macro() {
+        // Registered most specific first whatever the order they're written in
+        let router = routes! {
+            GET "/users/:id" => |_, _| async { Response::empty(Status::Ok) },
+            GET "/users/me" => |_, _| async { Response::empty(Status::NoContent) },
+            POST "/users/:name" => |_, _| async { Response::empty(Status::Created) },
+        };
+
+        let response = router.handle(request(Method::GET, "/users/me")).await;
+        assert_eq!(response.status, Status::NoContent);
+
+        let response = router.handle(request(Method::GET, "/users/1")).await;
+        assert_eq!(response.status, Status::Ok);
+
+        let response = router.handle(request(Method::POST, "/users/me")).await;
+        assert_eq!(response.status, Status::Created);
+    }
+
+    #[tokio::test]
+    async fn test_options_asterisk() {
+        let router = Router::new()
+            .get("/", |_, _| async { Response::empty(Status::Ok) })
+            .post("/items", |_, _| async { Response::empty(Status::Created) })
+            .delete("/items/:id", |_, _| async {
+                Response::empty(Status::NoContent)
+            })
+            .get("/items/:id", |_, _| async { Response::empty(Status::Ok) });
+
+        let response = router.handle(request(Method::OPTIONS, "*")).await;
+        assert_eq!(response.status, Status::Ok);
+        assert_eq!(
+            response.headers.get(&Header::Allow),
+            Some(&"GET, HEAD, POST, DELETE, OPTIONS".to_owned())
+        );
+
+        // Only OPTIONS may ask about `*`, it isn't a path
+        let response = router.handle(request(Method::GET, "*")).await;
+        assert_eq!(response.status, Status::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn test_method_not_allowed() {
+        let router = Router::new()
+            .get("/", |_, _| async { Response::empty(Status::Ok) })
+            .post("/", |_, _| async { Response::empty(Status::Created) })
+            .delete("/items/:id", |_, _| async {
+                Response::empty(Status::NoContent)
+            });
+
+        let response = router.handle(request(Method::PUT, "/")).await;
+        assert_eq!(response.status, Status::MethodNotAllowed);
+        assert_eq!(
+            response.headers.get(&Header::Allow),
+            Some(&"GET, HEAD, POST".to_owned())
+        );
+
+        let response = router.handle(request(Method::GET, "/items/1")).await;
+        assert_eq!(response.status, Status::MethodNotAllowed);
+        assert_eq!(
+            response.headers.get(&Header::Allow),
+            Some(&"DELETE".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_not_found_handlers() {
+        let router = Router::new().get("/", |_, _| async { Response::empty(Status::Ok) });
+
+        let response = router.handle(request(Method::GET, "/missing")).await;
+        assert_eq!(response.status, Status::NotFound);
+        assert_eq!(
+            response.headers.get(&Header::ContentType),
+            Some(&"text/plain".to_owned())
+        );
+        assert_eq!(response.body.as_bytes(), Some(&b"Not Found\n"[..]));
+
+        let response = router.handle(request(Method::POST, "/")).await;
+        assert_eq!(response.body.as_bytes(), Some(&b"Method Not Allowed\n"[..]));
+
+        let router = router
+            .not_found(|request: Request, _| async move {
+                Response::builder()
+                    .status(Status::NotFound)
+                    .header(Header::ContentType, "text/html")
+                    .body(format!("<h1>No {}</h1>", request.path))
+                    .build()
+            })
+            .method_not_allowed(|_, _| async {
+                Response::builder()
+                    .status(Status::MethodNotAllowed)
+                    .body("nope")
+                    .build()
+            });
+
+        let response = router.handle(request(Method::GET, "/missing")).await;
+        assert_eq!(response.status, Status::NotFound);
+        assert_eq!(
+            response.headers.get(&Header::ContentType),
+            Some(&"text/html".to_owned())
+        );
+        assert_eq!(response.body.as_bytes(), Some(&b"<h1>No /missing</h1>"[..]));
+
+        let response = router.handle(request(Method::POST, "/")).await;
+        assert_eq!(response.status, Status::MethodNotAllowed);
+        assert_eq!(response.body.as_bytes(), Some(&b"nope"[..]));
+        assert_eq!(
+            response.headers.get(&Header::Allow),
+            Some(&"GET, HEAD".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webdav_not_implemented() {
+        let router = Router::new()
+            .get("/", |_, _| async { Response::empty(Status::Ok) })
+            .route(Method::MKCOL, "/dir", |_, _| async {
+                Response::empty(Status::Created)
+            });
+
+        let response = router.handle(request(Method::PROPFIND, "/")).await;
+        assert_eq!(response.status, Status::NotImplemented);
+
+        let response = router.handle(request(Method::MKCOL, "/dir")).await;
+        assert_eq!(response.status, Status::Created);
+    }
+
+    #[tokio::test]
+    async fn test_params() {
+        let router = Router::new().get("/files/:filename", |_, params: Params| async move {
+            let filename = params.get("filename").unwrap_or_default();
+
+            Response::builder().body(filename).build()
+        });
+
+        let response = router.handle(request(Method::GET, "/files/foo.txt")).await;
+        assert_eq!(response.body.as_bytes(), Some(b"foo.txt".as_slice()));
+
+        let response = router.handle(request(Method::GET, "/files/")).await;
+        assert!(matches!(response.status, Status::NotFound));
+
+        let response = router.handle(request(Method::GET, "/files/a/b")).await;
+        assert!(matches!(response.status, Status::NotFound));
+
+        let response = router
+            .handle(request(Method::GET, "/files/hello%20world"))
+            .await;
+        assert_eq!(response.body.as_bytes(), Some(b"hello world".as_slice()));
+
+        let response = router.handle(request(Method::GET, "/files/%GG")).await;
+        assert!(matches!(response.status, Status::BadRequest));
+    }
+
+    #[tokio::test]
+    async fn test_handler() {
+        struct Greeter {
+            greeting: String,
+        }
+
+        impl Handler for Greeter {
+            fn call<'a>(
+                &'a self,
+                _: Request,
+                params: Params,
+            ) -> BoxFuture<'a, Result<Response, HttpError>> {
+                Box::pin(async move {
+                    let name = params.get("name").ok_or(HttpError::NotFound)?;
+
+                    Ok(Response::builder()
+                        .body(format!("{}, {name}", self.greeting))
+                        .build())
+                })
+            }
+        }
+
+        let router = Router::new().get(
+            "/greet/:name",
+            Greeter {
+                greeting: "Hello".to_owned(),
+            },
+        );
+
+        let response = router.handle(request(Method::GET, "/greet/teevik")).await;
+        assert_eq!(response.body.as_bytes(), Some(b"Hello, teevik".as_slice()));
+    }
+
+    #[tokio::test]
+    async fn test_empty_segments() {
+        let router = Router::new()
+            .get("/a/b", |_, _| async { Response::empty(Status::Ok) })
+            .get("/a//b", |_, _| async { Response::empty(Status::NoContent) });
+
+        let response = router.handle(request(Method::GET, "/a/b")).await;
+        assert_eq!(response.status, Status::Ok);
+
+        let response = router.handle(request(Method::GET, "/a//b")).await;
+        assert_eq!(response.status, Status::NoContent);
+
+        let response = router.handle(request(Method::GET, "/a/b/")).await;
+        assert_eq!(response.status, Status::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_redirect() {
+        let router = Router::new().redirect("/old", "/new");
+
+        let response = router.handle(request(Method::GET, "/old")).await;
+        assert_eq!(response.status, Status::PermanentRedirect);
+        assert_eq!(
+            response.headers.get(&Header::Location),
+            Some(&"/new".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_round_trip() {
+        let router = Router::new().post("/json", |request: Request, _| async move {
+            match request.json::<serde_json::Value>() {
+                Ok(value) => Response::json(&value).expect("serialize value"),
+                Err(_) => Response::empty(Status::BadRequest),
+            }
+        });
+
+        let value = serde_json::json!({ "name": "teevik", "tags": ["http", 1, null] });
+
+        let mut json_request = request(Method::POST, "/json");
+        json_request.body = serde_json::to_vec(&value).expect("serialize value");
+
+        let response = router.handle(json_request).await;
+        assert!(matches!(response.status, Status::Ok));
+        assert_eq!(
+            response.headers.get(&Header::ContentType),
+            Some(&"application/json".to_owned())
+        );
+
+        let body = response.body.as_bytes().expect("sized body");
+        let echoed: serde_json::Value = serde_json::from_slice(body).expect("parse body");
+        assert_eq!(echoed, value);
+
+        let mut invalid_request = request(Method::POST, "/json");
+        invalid_request.body = b"{".to_vec();
+
+        let response = router.handle(invalid_request).await;
+        assert!(matches!(response.status, Status::BadRequest));
+    }
+}
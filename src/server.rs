@@ -0,0 +1,785 @@
+#[cfg(feature = "http2")]
+use crate::http2;
+#[cfg(feature = "proxy")]
+use crate::proxy;
+use crate::{
+    config::ServerConfig,
+    date::format_http_date,
+    defaults,
+    error::HttpError,
+    header::Header,
+    health::Metrics,
+    rate_limit::RateLimiter,
+    request::{HttpVersion, Method, Request, RequestError},
+    request_id::RequestId,
+    response::{OnUpgrade, Response, ResponseBuilder, Status, Upgraded},
+    router::Router,
+    timing::Timings,
+};
+use anyhow::Context;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Instant, SystemTime},
+};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot, Semaphore},
+    task::JoinSet,
+    time::timeout,
+};
+use tokio_rustls::TlsAcceptor;
+use tracing::{Instrument, Span};
+
+/// Listens on `addr`, IPv4 connections included when it's the unspecified IPv6 address `::`
+///
+/// Whether `::` also accepts IPv4 otherwise depends on the system, e.g. `bindv6only` on Linux.
+pub fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    // Like `TcpListener::bind`, so restarts don't wait for old connections to time out
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        if let Err(err) = socket.set_only_v6(false) {
+            tracing::warn!("can't accept IPv4 connections on {addr}: {err}");
+        }
+    }
+
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+/// Listens on a Unix socket at `path`, replacing a socket left behind by a server that's no
+/// longer running
+#[cfg(unix)]
+pub fn bind_unix(path: &Path) -> io::Result<UnixListener> {
+    use std::{fs, os::unix::fs::FileTypeExt, os::unix::net};
+
+    let is_socket = fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+
+    // Nobody answering means the socket is stale, a live server keeps its socket
+    if is_socket
+        && net::UnixStream::connect(path)
+            .is_err_and(|err| err.kind() == io::ErrorKind::ConnectionRefused)
+    {
+        tracing::debug!(path = %path.display(), "removing stale socket");
+        fs::remove_file(path)?;
+    }
+
+    UnixListener::bind(path)
+}
+
+/// Where [`Server::run`] accepts connections from
+pub trait Listener: Send + Sync {
+    type Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+
+    /// The next connection, with the address of the peer if it has one
+    fn accept(&self)
+        -> impl Future<Output = io::Result<(Self::Stream, Option<SocketAddr>)>> + Send;
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    async fn accept(&self) -> io::Result<(TcpStream, Option<SocketAddr>)> {
+        let (stream, remote_addr) = TcpListener::accept(self).await?;
+        Ok((stream, Some(remote_addr)))
+    }
+}
+
+/// Peers on a Unix socket have no address, their requests have no
+/// [`client_ip`](Request::client_ip)
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+
+    async fn accept(&self) -> io::Result<(UnixStream, Option<SocketAddr>)> {
+        let (stream, _) = UnixListener::accept(self).await?;
+        Ok((stream, None))
+    }
+}
+
+/// Accepts connections and answers their requests with a [`Router`]
+pub struct Server {
+    router: Arc<Router>,
+    config: Arc<ServerConfig>,
+    rate_limiter: Arc<RateLimiter>,
+    metrics: Arc<Metrics>,
+    acceptor: Option<TlsAcceptor>,
+}
+
+impl Server {
+    /// Serves `router`, with [`defaults::routes`] added unless [`ServerConfig::serve_defaults`]
+    /// is off
+    pub fn new(router: Router, config: ServerConfig) -> Server {
+        let router = if config.serve_defaults {
+            defaults::routes(router)
+        } else {
+            router
+        };
+
+        Server {
+            router: Arc::new(router),
+            rate_limiter: Arc::new(RateLimiter::from_config(&config)),
+            config: Arc::new(config),
+            metrics: Arc::new(Metrics::new()),
+            acceptor: None,
+        }
+    }
+
+    /// Counts connections in `metrics`, e.g. the ones reported by [`crate::health::routes`]
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Server {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Serves HTTPS instead of plain HTTP
+    pub fn with_tls(mut self, acceptor: TlsAcceptor) -> Server {
+        self.acceptor = Some(acceptor);
+        self
+    }
+
+    /// Serves connections from `listener` until `shutdown` resolves, then lets in-flight
+    /// connections finish for up to [`ServerConfig::drain_timeout`]
+    pub async fn run<L, F>(self, listener: L, shutdown: F) -> anyhow::Result<()>
+    where
+        L: Listener,
+        F: Future<Output = anyhow::Result<()>>,
+    {
+        tokio::pin!(shutdown);
+
+        let mut connections = JoinSet::new();
+
+        let max_connections = self.config.max_connections.clamp(1, Semaphore::MAX_PERMITS);
+        let permits = Arc::new(Semaphore::new(max_connections));
+
+        loop {
+            // Connections past the limit wait in the listen backlog for a permit, rather than
+            // being accepted for sockets the process may not have
+            let accept = async {
+                let permit = permits.clone().acquire_owned().await;
+                (permit, listener.accept().await)
+            };
+
+            tokio::select! {
+                (permit, accepted) = accept => {
+                    let permit = permit.context("acquire connection permit")?;
+                    let (socket, remote_addr) = accepted.context("accept listener")?;
+
+                    tracing::debug!(
+                        remote_addr = remote_addr.map(display),
+                        "accepted new connection"
+                    );
+
+                    if permits.available_permits() == 0 {
+                        tracing::debug!(max_connections, "connection limit reached");
+                    }
+
+                    let router = self.router.clone();
+                    let rate_limiter = self.rate_limiter.clone();
+                    let config = self.config.clone();
+
+                    let acceptor = self.acceptor.clone();
+                    let connection = self.metrics.connection();
+
+                    connections.spawn(async move {
+                        // Counted as active and holding a permit until the connection is closed
+                        let _connection = connection;
+                        let _permit = permit;
+
+                        match acceptor {
+                            Some(acceptor) => {
+                                handle_tls_socket(
+                                    socket,
+                                    acceptor,
+                                    remote_addr,
+                                    router,
+                                    rate_limiter,
+                                    config,
+                                )
+                                .await
+                            }
+                            None => {
+                                handle_socket(socket, remote_addr, router, rate_limiter, config)
+                                    .await
+                            }
+                        }
+                    });
+                }
+                // Reap finished connections so the set doesn't grow forever
+                Some(_) = connections.join_next() => {}
+                result = &mut shutdown => {
+                    result?;
+                    break;
+                }
+            }
+        }
+
+        drop(listener);
+
+        tracing::info!(
+            connections = connections.len(),
+            "shutting down, draining connections"
+        );
+
+        let drain = async { while connections.join_next().await.is_some() {} };
+
+        if timeout(self.config.drain_timeout, drain).await.is_ok() {
+            tracing::info!("all connections drained");
+        } else {
+            tracing::warn!(
+                connections = connections.len(),
+                "drain timed out, aborting remaining connections"
+            );
+
+            connections.shutdown().await;
+        }
+
+        Ok(())
+    }
+}
+
+/// A request read ahead of the responses still being written
+struct Pipelined {
+    id: RequestId,
+    /// A response that doesn't need routing, e.g. for a malformed request
+    request: Result<Request, Response>,
+    keep_alive: bool,
+    /// Set for requests asking for an upgrade, the reader waits for it to learn whether the
+    /// connection still speaks HTTP
+    resume: Option<oneshot::Sender<bool>>,
+    /// Up to and including reading the request
+    timings: Timings,
+}
+
+/// A response waiting to be written in the order the requests arrived
+struct Answered {
+    response: Response,
+    /// The span of the request, with its ID
+    span: Span,
+    head: bool,
+    /// Of the request, HTTP/1.0 clients can't read chunked bodies
+    version: HttpVersion,
+    keep_alive: bool,
+    resume: Option<oneshot::Sender<bool>>,
+    start: Instant,
+}
+
+/// Answers requests on a connection until it's closed, or until a response upgrades it to
+/// another protocol, in which case the upgrade is returned to take over the connection
+///
+/// Pipelined requests are read while earlier ones are being answered, up to
+/// [`ServerConfig::pipeline_depth`] of them, and answered in order. A connection that fails
+/// is answered with an error only if no response is pending or partly written.
+async fn serve<R, W>(
+    reader: &mut BufReader<R>,
+    writer: &mut W,
+    remote_addr: Option<SocketAddr>,
+    router: &Router,
+    rate_limiter: &RateLimiter,
+    config: &ServerConfig,
+) -> anyhow::Result<Option<OnUpgrade>>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let depth = config.pipeline_depth.max(1);
+    let (requests_tx, requests_rx) = mpsc::channel(depth);
+    let (responses_tx, responses_rx) = mpsc::channel(depth);
+    let in_flight = AtomicUsize::new(0);
+
+    let answer = async {
+        answer_requests(requests_rx, responses_tx, router, rate_limiter, config).await;
+        Ok(())
+    };
+
+    let result = tokio::try_join!(
+        read_requests(reader, requests_tx, remote_addr, &in_flight, config),
+        answer,
+        write_responses(writer, responses_rx, &in_flight),
+    );
+
+    match result {
+        Ok((_, _, upgrade)) => Ok(upgrade),
+        // Every earlier response went out whole, so the failed request can still be answered
+        Err(err) if in_flight.load(Ordering::Acquire) == 0 => {
+            // Best effort, the connection might already be gone
+            let response = Response::from(HttpError::from(err.context("handle connection")));
+            let _ = response.write_to(writer).await;
+
+            Ok(None)
+        }
+        // A response is waiting or partly written, anything more would be read as part of it
+        Err(err) => Err(err),
+    }
+}
+
+/// Parses requests into the pipeline until the connection is closed or stops being HTTP
+async fn read_requests<R>(
+    reader: &mut BufReader<R>,
+    requests: mpsc::Sender<Pipelined>,
+    remote_addr: Option<SocketAddr>,
+    in_flight: &AtomicUsize,
+    config: &ServerConfig,
+) -> anyhow::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        // Wait for the start of the next request, the connection may idle until then
+        loop {
+            match timeout(config.keep_alive_timeout, reader.fill_buf()).await {
+                // Client closed the connection
+                Ok(Ok([])) => return Ok(()),
+                Ok(Ok(_)) => break,
+                Ok(Err(err)) => return Err(err).context("read request"),
+                // Only idle once the earlier requests have been answered
+                Err(_) if in_flight.load(Ordering::Acquire) > 0 => {}
+                // Idle for too long
+                Err(_) => return Ok(()),
+            }
+        }
+
+        let mut timings = Timings::new();
+
+        // Failures after the head is parsed carry the request's ID so the error response
+        // still echoes it
+        let next_request = async {
+            let mut request = Request::parse_head(reader, config)
+                .await
+                .map_err(|err| (None, err))?;
+            let id = identify(&mut request, remote_addr, config);
+            let with_id = |err| (Some(id.clone()), err);
+
+            request.check_host().map_err(with_id)?;
+
+            if request.expects_continue(config).map_err(with_id)? {
+                // Answered in turn like any other response, the body follows it
+                in_flight.fetch_add(1, Ordering::AcqRel);
+                let _ = requests
+                    .send(Pipelined {
+                        id: id.clone(),
+                        request: Err(Response::empty(Status::Continue)),
+                        keep_alive: true,
+                        resume: None,
+                        timings: Timings::new(),
+                    })
+                    .await;
+            }
+
+            timeout(config.read_body_timeout, request.read_body(reader, config))
+                .await
+                .map_err(|_| with_id(RequestError::BodyTimeout))?
+                .map_err(with_id)?;
+
+            Ok(request)
+        };
+
+        let parsed = timeout(config.connection_timeout, next_request).await;
+        timings.record("parse");
+
+        let request = match parsed {
+            Ok(Ok(request)) => request,
+            Err(_) => {
+                tracing::warn!("timed out waiting for the request, closing connection");
+                return Ok(());
+            }
+            // Client closed the connection
+            Ok(Err((_, RequestError::NoRequestLine))) => return Ok(()),
+            Ok(Err((id, err))) => {
+                let Some(status) = err.status() else {
+                    return Err(err).context("parse request");
+                };
+
+                // The rest of the stream can't be trusted after a malformed request
+                in_flight.fetch_add(1, Ordering::AcqRel);
+                let _ = requests
+                    .send(Pipelined {
+                        id: id.unwrap_or_default(),
+                        request: Err(Response::empty(status)),
+                        keep_alive: false,
+                        resume: None,
+                        timings,
+                    })
+                    .await;
+
+                return Ok(());
+            }
+        };
+
+        let connection = request.headers.get_all(&Header::Connection);
+        let has_option = |option: &str| {
+            connection
+                .iter()
+                .any(|connection| connection.eq_ignore_ascii_case(option))
+        };
+
+        // HTTP/1.0 bodies of unknown length end when the connection closes, so those
+        // connections are never kept alive
+        let keep_alive = request.version.keeps_alive_by_default() && !has_option("close");
+
+        // What follows an upgrade or tunnel request is only HTTP if it's refused
+        let takes_over =
+            request.headers.contains(&Header::Upgrade) || request.method == Method::CONNECT;
+
+        let (resume, resumed) = if takes_over {
+            let (resume, resumed) = oneshot::channel();
+            (Some(resume), Some(resumed))
+        } else {
+            (None, None)
+        };
+
+        let id = request
+            .extensions
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_default();
+
+        in_flight.fetch_add(1, Ordering::AcqRel);
+        let pipelined = Pipelined {
+            id,
+            request: Ok(request),
+            keep_alive,
+            resume,
+            timings,
+        };
+
+        if requests.send(pipelined).await.is_err() || !keep_alive {
+            return Ok(());
+        }
+
+        if let Some(resumed) = resumed {
+            if !resumed.await.unwrap_or(false) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Answers the pipelined requests one at a time, so responses keep the order of the requests
+async fn answer_requests(
+    mut requests: mpsc::Receiver<Pipelined>,
+    responses: mpsc::Sender<Answered>,
+    router: &Router,
+    rate_limiter: &RateLimiter,
+    config: &ServerConfig,
+) {
+    while let Some(pipelined) = requests.recv().await {
+        let start = Instant::now();
+
+        let Pipelined {
+            id,
+            request,
+            keep_alive,
+            resume,
+            mut timings,
+        } = pipelined;
+
+        // Waiting for earlier requests to be answered isn't part of answering this one
+        timings.skip();
+
+        let span = tracing::info_span!("request", %id);
+
+        let (mut response, head, version) = match request {
+            Ok(request) => {
+                span.in_scope(
+                    || tracing::debug!(method = ?request.method, path = %request.path, "request"),
+                );
+
+                let head = request.method == Method::HEAD;
+                let version = request.version;
+
+                let response = answer(request, router, rate_limiter, config)
+                    .instrument(span.clone())
+                    .await;
+                timings.record("handler");
+
+                (response, head, version)
+            }
+            Err(response) => (response, false, HttpVersion::HTTP_1_1),
+        };
+
+        // The interim response stays bare, the final one carries the ID
+        if response.status != Status::Continue {
+            finish(&mut response, &id, config);
+        }
+
+        if !keep_alive {
+            response
+                .headers
+                .insert(Header::Connection, "close".to_owned());
+        }
+
+        timings.record("serialize");
+        if config.server_timing && response.status != Status::Continue {
+            response
+                .headers
+                .insert(Header::ServerTiming, timings.to_string());
+        }
+
+        let answered = Answered {
+            response,
+            span,
+            head,
+            version,
+            keep_alive,
+            resume,
+            start,
+        };
+
+        if responses.send(answered).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Records where `request` came from and gives it an ID, which only a trusted proxy may pick,
+/// like the client address
+pub(crate) fn identify(
+    request: &mut Request,
+    remote_addr: Option<SocketAddr>,
+    config: &ServerConfig,
+) -> RequestId {
+    let trusted = remote_addr.is_some_and(|addr| config.trust_proxy.is_trusted(addr.ip()));
+
+    let id = RequestId::from_headers(&request.headers)
+        .filter(|_| trusted)
+        .unwrap_or_default();
+    request.extensions.insert(id.clone());
+
+    request.remote_addr = remote_addr;
+    request.forwarded_for = remote_addr.and_then(|addr| {
+        config
+            .trust_proxy
+            .forwarded_for(addr.ip(), &request.headers)
+    });
+
+    id
+}
+
+/// Adds the headers every final response carries
+pub(crate) fn finish(response: &mut Response, id: &RequestId, config: &ServerConfig) {
+    response.headers.insert(Header::RequestId, id.to_string());
+
+    if !config.server_name.is_empty() {
+        response.add_via(&config.server_name);
+    }
+
+    if let Some(server) = &config.server_header {
+        response.headers.insert(Header::Server, server.clone());
+    }
+
+    // Keeps a `Date` passed on from upstream, which is when the response was made
+    response
+        .headers
+        .get_or_insert_with(Header::Date, || format_http_date(SystemTime::now()));
+}
+
+/// The response to a single request
+pub(crate) async fn answer(
+    request: Request,
+    router: &Router,
+    rate_limiter: &RateLimiter,
+    config: &ServerConfig,
+) -> Response {
+    // Unix socket peers have no address to limit by
+    let limited = request.client_ip().map(|ip| rate_limiter.check(ip));
+
+    if let Some(Err(retry_after)) = limited {
+        tracing::debug!(?retry_after, "rate limited");
+
+        // Retry-After only takes whole seconds
+        let retry_after = retry_after.as_secs_f64().ceil() as u64;
+
+        return Response::builder()
+            .status(Status::TooManyRequests)
+            .header(Header::RetryAfter, retry_after.to_string())
+            .build();
+    }
+
+    match request.method {
+        Method::TRACE if config.allow_trace => request.trace(),
+        Method::TRACE => method_not_allowed(&request, router).build(),
+        #[cfg(feature = "proxy")]
+        Method::CONNECT => proxy::connect(&request, config.connection_timeout).await,
+        // Not a proxy, so there's nothing to tunnel to
+        #[cfg(not(feature = "proxy"))]
+        Method::CONNECT => method_not_allowed(&request, router)
+            .header(Header::ContentType, "text/plain")
+            .body("CONNECT is not supported, this server is not a proxy")
+            .build(),
+        _ => router.handle(request).await,
+    }
+}
+
+/// Refuses a method the server answers itself instead of the routes, with the methods the
+/// routes do answer for the path in `Allow`
+fn method_not_allowed(request: &Request, router: &Router) -> ResponseBuilder {
+    Response::builder()
+        .status(Status::MethodNotAllowed)
+        .header(Header::Allow, router.allowed(request))
+}
+
+/// Writes the answered responses in order, returning the upgrade of a response that takes
+/// over the connection
+async fn write_responses<W>(
+    writer: &mut W,
+    mut responses: mpsc::Receiver<Answered>,
+    in_flight: &AtomicUsize,
+) -> anyhow::Result<Option<OnUpgrade>>
+where
+    W: AsyncWrite + Unpin,
+{
+    while let Some(answered) = responses.recv().await {
+        let Answered {
+            mut response,
+            span,
+            head,
+            version,
+            keep_alive,
+            resume,
+            start,
+        } = answered;
+
+        let status = response.status;
+        let upgrade = response.upgrade.take();
+
+        if version < HttpVersion::HTTP_1_1 {
+            response
+                .write_http_1_0_to(writer, head)
+                .instrument(span.clone())
+                .await
+        } else if head {
+            response
+                .write_head_to(writer)
+                .instrument(span.clone())
+                .await
+        } else {
+            response.write_to(writer).instrument(span.clone()).await
+        }
+        .context("write response")?;
+
+        in_flight.fetch_sub(1, Ordering::AcqRel);
+
+        span.in_scope(|| tracing::info!(%status, latency = ?start.elapsed(), "response"));
+
+        // Only requests the reader stopped after may take over the connection
+        let upgrade = upgrade.filter(|_| resume.is_some() && !head);
+
+        if let Some(resume) = resume {
+            let _ = resume.send(upgrade.is_none());
+        }
+
+        if upgrade.is_some() {
+            return Ok(upgrade);
+        }
+
+        if !keep_alive {
+            return Ok(None);
+        }
+    }
+
+    Ok(None)
+}
+
+#[tracing::instrument(skip_all, fields(remote_addr = remote_addr.map(display)))]
+async fn handle_socket<S>(
+    stream: S,
+    remote_addr: Option<SocketAddr>,
+    router: Arc<Router>,
+    rate_limiter: Arc<RateLimiter>,
+    config: Arc<ServerConfig>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+
+    let result = serve(
+        &mut reader,
+        &mut writer,
+        remote_addr,
+        &router,
+        &rate_limiter,
+        &config,
+    )
+    .await;
+
+    match result {
+        Ok(Some(upgrade)) => {
+            upgrade(Upgraded {
+                reader: Box::new(reader),
+                writer: Box::new(writer),
+            })
+            .await;
+            Ok(())
+        }
+        Ok(None) => Ok(()),
+        Err(err) => {
+            tracing::debug!("closing connection: {err:#}");
+            Ok(())
+        }
+    }
+}
+
+/// Completes the TLS handshake before handing the connection to [`handle_socket`]
+async fn handle_tls_socket<S>(
+    socket: S,
+    acceptor: TlsAcceptor,
+    remote_addr: Option<SocketAddr>,
+    router: Arc<Router>,
+    rate_limiter: Arc<RateLimiter>,
+    config: Arc<ServerConfig>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let stream = match timeout(config.connection_timeout, acceptor.accept(socket)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(err)) => {
+            tracing::warn!(
+                remote_addr = remote_addr.map(display),
+                "tls handshake failed: {err}"
+            );
+            return Ok(());
+        }
+        Err(_) => {
+            tracing::warn!(
+                remote_addr = remote_addr.map(display),
+                "tls handshake timed out"
+            );
+            return Ok(());
+        }
+    };
+
+    let protocol = stream.get_ref().1.alpn_protocol();
+
+    tracing::debug!(
+        remote_addr = remote_addr.map(display),
+        protocol = protocol.map(String::from_utf8_lossy).as_deref(),
+        "negotiated protocol"
+    );
+
+    #[cfg(feature = "http2")]
+    if protocol == Some(b"h2") {
+        return http2::serve(stream, remote_addr, router, rate_limiter, config).await;
+    }
+
+    handle_socket(stream, remote_addr, router, rate_limiter, config).await
+}
@@ -0,0 +1,170 @@
+use crate::{
+    header::Header,
+    response::{Response, ResponseBuilder},
+};
+use anyhow::Context;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use std::{fmt::Write, io};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_util::io::StreamReader;
+
+/// One server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The event type, `message` on the client if `None`
+    pub event: Option<String>,
+    pub data: String,
+}
+
+impl SseEvent {
+    /// A `message` event
+    pub fn message(data: impl Into<String>) -> SseEvent {
+        SseEvent {
+            event: None,
+            data: data.into(),
+        }
+    }
+
+    pub fn new(event: impl Into<String>, data: impl Into<String>) -> SseEvent {
+        SseEvent {
+            event: Some(event.into()),
+            data: data.into(),
+        }
+    }
+
+    /// The event in the `text/event-stream` format, ending with the blank line
+    pub fn encode(&self) -> String {
+        let mut encoded = String::new();
+
+        if let Some(event) = &self.event {
+            // A line break would end the field and let the rest pass for other fields
+            let event = event.replace(['\r', '\n'], "");
+            let _ = writeln!(encoded, "event: {event}");
+        }
+
+        // Each line of the data needs its own field, the client joins them with newlines. It
+        // breaks lines at `\r\n`, `\r` and `\n` alike.
+        for line in self
+            .data
+            .split("\r\n")
+            .flat_map(|line| line.split(['\r', '\n']))
+        {
+            let _ = writeln!(encoded, "data: {line}");
+        }
+
+        encoded.push('\n');
+        encoded
+    }
+}
+
+/// Writes events to an open `text/event-stream` body
+pub struct SseStream<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> SseStream<W> {
+    pub fn new(writer: W) -> SseStream<W> {
+        SseStream { writer }
+    }
+
+    pub async fn send_event(&mut self, event: &str, data: &str) -> anyhow::Result<()> {
+        self.send(&SseEvent::new(event, data)).await
+    }
+
+    pub async fn send(&mut self, event: &SseEvent) -> anyhow::Result<()> {
+        self.writer
+            .write_all(event.encode().as_bytes())
+            .await
+            .context("write event")?;
+        self.writer.flush().await.context("flush event")
+    }
+}
+
+/// The headers of an event stream, which must not be cached
+fn builder() -> ResponseBuilder {
+    Response::builder()
+        .header(Header::ContentType, "text/event-stream")
+        .header(Header::CacheControl, "no-cache")
+}
+
+impl Response {
+    /// Streams `events` to the client as they're produced, the response ends with the stream
+    pub fn sse<S>(events: S) -> Response
+    where
+        S: Stream<Item = SseEvent> + Send + 'static,
+    {
+        let chunks = events.map(|event| Ok::<_, io::Error>(Bytes::from(event.encode())));
+
+        builder()
+            .chunked_body(StreamReader::new(Box::pin(chunks)))
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            SseEvent::new("update", "line one\nline two").encode(),
+            "event: update\ndata: line one\ndata: line two\n\n"
+        );
+        assert_eq!(SseEvent::message("hi").encode(), "data: hi\n\n");
+        assert_eq!(
+            SseEvent::message("a\r\nb\rc\n\nd").encode(),
+            "data: a\ndata: b\ndata: c\ndata: \ndata: d\n\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_injection() {
+        // Neither can start fields of their own
+        assert_eq!(
+            SseEvent::new("x\nid: 1", "hi").encode(),
+            "event: xid: 1\ndata: hi\n\n"
+        );
+        assert_eq!(
+            SseEvent::new("x\r\nretry: 0\r", "hi").encode(),
+            "event: xretry: 0\ndata: hi\n\n"
+        );
+        assert_eq!(
+            SseEvent::message("a\revent: other").encode(),
+            "data: a\ndata: event: other\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sse_response() {
+        let events =
+            futures_util::stream::iter([SseEvent::new("tick", "1"), SseEvent::new("tick", "2")]);
+
+        let mut output = Vec::new();
+        Response::sse(events)
+            .write_to(&mut output)
+            .await
+            .expect("write response");
+
+        let output = String::from_utf8(output).expect("utf-8");
+        assert!(output.contains("Content-Type: text/event-stream\r\n"));
+        assert!(output.contains("Cache-Control: no-cache\r\n"));
+        assert!(output.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(output.ends_with(
+            "\r\n\r\n15\r\nevent: tick\ndata: 1\n\n\r\n15\r\nevent: tick\ndata: 2\n\n\r\n0\r\n\r\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream() {
+        let mut output = Vec::new();
+
+        SseStream::new(&mut output)
+            .send_event("greeting", "hello")
+            .await
+            .expect("send event");
+
+        assert_eq!(output, b"event: greeting\ndata: hello\n\n");
+    }
+}
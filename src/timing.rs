@@ -0,0 +1,92 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    time::{Duration, Instant},
+};
+
+/// How long each stage of answering a request took, sent as `Server-Timing` so browser
+/// developer tools can show where the time went
+///
+/// Displays as the header value, e.g. `parse;dur=0.23, handler;dur=4.10` in milliseconds.
+#[derive(Debug, Clone)]
+pub struct Timings {
+    /// When the stage being timed started
+    since: Instant,
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// Starts timing the first stage
+    pub fn new() -> Timings {
+        Timings {
+            since: Instant::now(),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Ends the stage being timed as `name` and starts the next one
+    pub fn record(&mut self, name: &'static str) {
+        let now = Instant::now();
+
+        self.stages.push((name, now - self.since));
+        self.since = now;
+    }
+
+    /// Starts the next stage now, leaving out the time since the last one, e.g. spent waiting
+    /// for earlier pipelined requests
+    pub fn skip(&mut self) {
+        self.since = Instant::now();
+    }
+
+    pub fn stages(&self) -> &[(&'static str, Duration)] {
+        &self.stages
+    }
+}
+
+impl Default for Timings {
+    fn default() -> Timings {
+        Timings::new()
+    }
+}
+
+impl Display for Timings {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, (name, duration)) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{name};dur={:.2}", duration.as_secs_f64() * 1000.0)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings() {
+        let mut timings = Timings::new();
+        timings.record("parse");
+        timings.skip();
+        timings.record("handler");
+
+        let names: Vec<_> = timings.stages().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["parse", "handler"]);
+
+        let timings = Timings {
+            since: Instant::now(),
+            stages: vec![
+                ("parse", Duration::from_micros(230)),
+                ("handler", Duration::from_micros(4100)),
+                ("serialize", Duration::from_micros(50)),
+            ],
+        };
+        assert_eq!(
+            timings.to_string(),
+            "parse;dur=0.23, handler;dur=4.10, serialize;dur=0.05"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::Context;
+use std::{path::Path, sync::Arc};
+use tokio_rustls::{
+    rustls::{
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+        ServerConfig as TlsConfig,
+    },
+    TlsAcceptor,
+};
+
+/// Builds an acceptor from a PEM certificate chain and private key
+pub fn acceptor(cert_path: &Path, key_path: &Path) -> anyhow::Result<TlsAcceptor> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .with_context(|| format!("read certificate {}", cert_path.display()))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("parse certificate {}", cert_path.display()))?;
+
+    if certs.is_empty() {
+        anyhow::bail!("no certificates found in {}", cert_path.display());
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .with_context(|| format!("read private key {}", key_path.display()))?;
+
+    let mut config = TlsConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("certificate doesn't match private key")?;
+
+    // Clients that don't negotiate a protocol speak HTTP/1.1
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    #[cfg(feature = "http2")]
+    config.alpn_protocols.insert(0, b"h2".to_vec());
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_certificate() {
+        let result = acceptor(Path::new("missing-cert.pem"), Path::new("missing-key.pem"));
+
+        assert!(result.is_err());
+    }
+}
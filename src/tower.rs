@@ -0,0 +1,118 @@
+use crate::{
+    error::HttpError,
+    request::Request,
+    response::Response,
+    router::{BoxFuture, Router},
+};
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::PollSemaphore;
+use tower_service::Service;
+
+/// A [`Router`] as a tower [`Service`], so it can be wrapped in tower middleware
+///
+/// Routers always answer with a response, errors included, so the service never fails.
+pub struct RouterService {
+    router: Arc<Router>,
+    /// Requests answered at once, `None` for no limit
+    capacity: Option<PollSemaphore>,
+    /// Taken by `poll_ready` for the next call
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl RouterService {
+    pub fn new(router: Router) -> RouterService {
+        RouterService {
+            router: Arc::new(router),
+            capacity: None,
+            permit: None,
+        }
+    }
+
+    /// Isn't ready while `capacity` requests are being answered, across all clones
+    pub fn with_capacity(mut self, capacity: usize) -> RouterService {
+        let capacity = capacity.clamp(1, Semaphore::MAX_PERMITS);
+
+        self.capacity = Some(PollSemaphore::new(Arc::new(Semaphore::new(capacity))));
+        self
+    }
+}
+
+/// Clones share the router and its capacity, but not a permit taken by `poll_ready`
+impl Clone for RouterService {
+    fn clone(&self) -> RouterService {
+        RouterService {
+            router: self.router.clone(),
+            capacity: self.capacity.clone(),
+            permit: None,
+        }
+    }
+}
+
+impl Service<Request> for RouterService {
+    type Response = Response;
+    type Error = HttpError;
+    type Future = BoxFuture<'static, Result<Response, HttpError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), HttpError>> {
+        let Some(capacity) = &mut self.capacity else {
+            return Poll::Ready(Ok(()));
+        };
+
+        if self.permit.is_none() {
+            // The semaphore is never closed, so there's always a permit eventually
+            self.permit = match capacity.poll_acquire(cx) {
+                Poll::Ready(permit) => permit,
+                Poll::Pending => return Poll::Pending,
+            };
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let router = self.router.clone();
+        let permit = self.permit.take();
+
+        Box::pin(async move {
+            let response = router.handle(request).await;
+            drop(permit);
+
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{request::Method, response::Status};
+    use std::future::poll_fn;
+
+    #[tokio::test]
+    async fn test_router_service() {
+        let router = Router::new().get("/", |_, _| async { Response::empty(Status::Ok) });
+        let mut service = RouterService::new(router).with_capacity(1);
+
+        poll_fn(|cx| service.poll_ready(cx)).await.expect("ready");
+        let first = service.call(Request::new(Method::GET, "/"));
+
+        // The first call holds the only permit until it's answered
+        let mut other = service.clone();
+        let ready = poll_fn(|cx| Poll::Ready(other.poll_ready(cx))).await;
+        assert!(ready.is_pending());
+
+        let response = first.await.expect("response");
+        assert_eq!(response.status, Status::Ok);
+
+        poll_fn(|cx| other.poll_ready(cx)).await.expect("ready");
+        let response = other
+            .call(Request::new(Method::GET, "/missing"))
+            .await
+            .expect("response");
+        assert_eq!(response.status, Status::NotFound);
+    }
+}
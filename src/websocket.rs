@@ -0,0 +1,461 @@
+use crate::{
+    header::Header,
+    request::{Method, Request},
+    response::{Response, Status, Upgraded},
+};
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+use std::future::Future;
+use tokio::io::{AsyncBufRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Appended to `Sec-WebSocket-Key` before hashing, from RFC 6455
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Larger frames and fragmented messages are refused
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The only version of the protocol there is, from RFC 6455
+const VERSION: &str = "13";
+
+/// Pings, pongs and close frames can't be any longer
+const MAX_CONTROL_PAYLOAD: u64 = 125;
+
+/// Close status for a frame breaking the protocol
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+
+/// Close status for a message too large to handle
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// The `Sec-WebSocket-Accept` value proving the handshake for `key` was understood
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(GUID.as_bytes());
+
+    STANDARD.encode(hasher.finalize())
+}
+
+/// Checks that `request` asks for a WebSocket upgrade, returning its `Sec-WebSocket-Key`
+fn handshake_key(request: &Request) -> Option<&str> {
+    let has = |header: &Header, token: &str| {
+        request
+            .headers
+            .get_all(header)
+            .iter()
+            .any(|value| value.eq_ignore_ascii_case(token))
+    };
+
+    if request.method != Method::GET
+        || !has(&Header::Upgrade, "websocket")
+        || !has(&Header::Connection, "upgrade")
+    {
+        return None;
+    }
+
+    request.headers.get_first(&Header::SecWebSocketKey)
+}
+
+/// Answers a WebSocket handshake, `on_upgrade` gets the connection once the response is sent
+///
+/// Requests that aren't a valid handshake get 400 Bad Request, with the supported version in
+/// `Sec-WebSocket-Version` if they ask for another one.
+pub fn upgrade<F, Fut>(request: &Request, on_upgrade: F) -> Response
+where
+    F: FnOnce(WebSocketConn) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let Some(key) = handshake_key(request) else {
+        return Response::empty(Status::BadRequest);
+    };
+
+    if request.headers.get_first(&Header::SecWebSocketVersion) != Some(VERSION) {
+        return Response::builder()
+            .status(Status::BadRequest)
+            .header(Header::SecWebSocketVersion, VERSION)
+            .build();
+    }
+
+    let mut response = Response::builder()
+        .status(Status::SwitchingProtocols)
+        .header(Header::Upgrade, "websocket")
+        .header(Header::Connection, "Upgrade")
+        .header(Header::SecWebSocketAccept, accept_key(key))
+        .build();
+
+    response.upgrade = Some(Box::new(move |Upgraded { reader, writer }| {
+        Box::pin(on_upgrade(WebSocketConn::new(reader, writer)))
+    }));
+
+    response
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_byte(byte: u8) -> Option<Opcode> {
+        match byte {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+}
+
+/// A message received from the client, fragmented messages are reassembled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+    /// Already answered with a pong
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// Already answered with a close frame, the connection should be dropped
+    Close,
+}
+
+/// A connection speaking RFC 6455 framing after a successful [`upgrade`]
+pub struct WebSocketConn {
+    reader: Box<dyn AsyncBufRead + Send + Unpin>,
+    writer: Box<dyn AsyncWrite + Send + Unpin>,
+    /// The opcode and data of a fragmented message still missing its final frame
+    fragments: Option<(Opcode, Vec<u8>)>,
+}
+
+impl WebSocketConn {
+    pub fn new(
+        reader: impl AsyncBufRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> WebSocketConn {
+        WebSocketConn {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            fragments: None,
+        }
+    }
+
+    pub async fn send_text(&mut self, text: &str) -> anyhow::Result<()> {
+        self.send(Opcode::Text, text.as_bytes()).await
+    }
+
+    pub async fn send_binary(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.send(Opcode::Binary, data).await
+    }
+
+    pub async fn send_close(&mut self) -> anyhow::Result<()> {
+        self.send(Opcode::Close, &[]).await
+    }
+
+    /// Closes the connection with `status` because of something the client sent
+    async fn fail(&mut self, status: u16, reason: String) -> anyhow::Error {
+        // The connection is given up on either way
+        let _ = self.send(Opcode::Close, &status.to_be_bytes()).await;
+
+        anyhow::anyhow!(reason)
+    }
+
+    /// Reads the next message, `None` once the client has closed the connection
+    pub async fn recv_frame(&mut self) -> anyhow::Result<Option<Frame>> {
+        loop {
+            let Some((fin, opcode, payload)) = self.read_frame().await? else {
+                return Ok(None);
+            };
+
+            match opcode {
+                Opcode::Ping => {
+                    self.send(Opcode::Pong, &payload).await?;
+                    return Ok(Some(Frame::Ping(payload)));
+                }
+                Opcode::Pong => return Ok(Some(Frame::Pong(payload))),
+                Opcode::Close => {
+                    self.send_close().await?;
+                    return Ok(Some(Frame::Close));
+                }
+                Opcode::Continuation => {
+                    let (_, data) = self
+                        .fragments
+                        .as_mut()
+                        .context("continuation without a message")?;
+
+                    if data.len() + payload.len() > MAX_MESSAGE_SIZE {
+                        anyhow::bail!("message exceeds {MAX_MESSAGE_SIZE} bytes");
+                    }
+                    data.extend_from_slice(&payload);
+                }
+                Opcode::Text | Opcode::Binary => {
+                    if self.fragments.is_some() {
+                        anyhow::bail!("new message before the previous one was finished");
+                    }
+
+                    self.fragments = Some((opcode, payload));
+                }
+            }
+
+            if fin {
+                if let Some((opcode, data)) = self.fragments.take() {
+                    return Ok(Some(match opcode {
+                        Opcode::Text => {
+                            Frame::Text(String::from_utf8(data).context("text isn't utf-8")?)
+                        }
+                        _ => Frame::Binary(data),
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Reads one raw frame, unmasking its payload
+    async fn read_frame(&mut self) -> anyhow::Result<Option<(bool, Opcode, Vec<u8>)>> {
+        let mut head = [0; 2];
+        match self.reader.read_exact(&mut head).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err).context("read frame"),
+        }
+
+        let fin = head[0] & 0x80 != 0;
+        // No extensions are negotiated, so none of their bits may be set
+        let rsv = head[0] & 0x70;
+        let masked = head[1] & 0x80 != 0;
+
+        if rsv != 0 {
+            let reason = format!("reserved bits {rsv:#04x} set");
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, reason).await);
+        }
+
+        let Some(opcode) = Opcode::from_byte(head[0] & 0x0F) else {
+            let reason = format!("unknown opcode {:#x}", head[0] & 0x0F);
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, reason).await);
+        };
+
+        // Clients must mask every frame
+        if !masked {
+            let reason = "unmasked frame from client".to_owned();
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, reason).await);
+        }
+
+        let len = match head[1] & 0x7F {
+            126 => self.reader.read_u16().await?.into(),
+            127 => self.reader.read_u64().await?,
+            len => len.into(),
+        };
+
+        let control = matches!(opcode, Opcode::Close | Opcode::Ping | Opcode::Pong);
+
+        // Control frames can come between the fragments of a message, so they can't be fragmented
+        if control && (!fin || len > MAX_CONTROL_PAYLOAD) {
+            let reason = format!("fragmented or {len} byte {opcode:?} frame");
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, reason).await);
+        }
+
+        if len > MAX_MESSAGE_SIZE as u64 {
+            let reason = format!("frame of {len} bytes exceeds {MAX_MESSAGE_SIZE} bytes");
+            return Err(self.fail(CLOSE_TOO_BIG, reason).await);
+        }
+
+        let mut mask = [0; 4];
+        self.reader.read_exact(&mut mask).await?;
+
+        let mut payload = vec![0; len as usize];
+        self.reader.read_exact(&mut payload).await?;
+
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok(Some((fin, opcode, payload)))
+    }
+
+    /// Writes `payload` as a single unmasked frame, as servers must
+    async fn send(&mut self, opcode: Opcode, payload: &[u8]) -> anyhow::Result<()> {
+        let mut frame = vec![0x80 | opcode.to_byte()];
+
+        match payload.len() {
+            len @ 0..=125 => frame.push(len as u8),
+            len @ 126..=0xFFFF => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        frame.extend_from_slice(payload);
+
+        self.writer.write_all(&frame).await.context("write frame")?;
+        self.writer.flush().await.context("flush frame")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tokio::io::BufReader;
+
+    /// A masked client frame, `opcode` may also set reserved bits
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+        }
+
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+
+        frame
+    }
+
+    #[test]
+    fn test_accept_key() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let mut request = Request::new(Method::GET, "/ws");
+        request.headers.append(Header::Upgrade, "websocket");
+        request
+            .headers
+            .append(Header::Connection, "keep-alive, Upgrade");
+        request
+            .headers
+            .append(Header::SecWebSocketKey, "dGhlIHNhbXBsZSBub25jZQ==");
+
+        // Without a version, or with one that isn't supported
+        let response = upgrade(&request, |_| async {});
+        assert_eq!(response.status, Status::BadRequest);
+        assert_eq!(
+            response.headers.get(&Header::SecWebSocketVersion),
+            Some(&"13".to_owned())
+        );
+
+        request.headers.append(Header::SecWebSocketVersion, "8");
+        let response = upgrade(&request, |_| async {});
+        assert_eq!(response.status, Status::BadRequest);
+        assert!(response.upgrade.is_none());
+
+        request.headers.insert(Header::SecWebSocketVersion, "13");
+        let response = upgrade(&request, |_| async {});
+        assert_eq!(response.status, Status::SwitchingProtocols);
+        assert!(response.upgrade.is_some());
+
+        let response = upgrade(&Request::new(Method::GET, "/ws"), |_| async {});
+        assert_eq!(response.status, Status::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn test_frames() {
+        let input = [
+            client_frame(false, 0x1, b"hel"),
+            client_frame(true, 0x9, b"ping"),
+            client_frame(true, 0x0, b"lo"),
+            client_frame(true, 0x8, b""),
+        ]
+        .concat();
+
+        let (writer, mut output) = tokio::io::duplex(1024);
+        let mut conn = WebSocketConn::new(BufReader::new(Cursor::new(input)), writer);
+
+        // Control frames may arrive in the middle of a fragmented message
+        assert_eq!(
+            conn.recv_frame().await.expect("read ping"),
+            Some(Frame::Ping(b"ping".to_vec()))
+        );
+        assert_eq!(
+            conn.recv_frame().await.expect("read text"),
+            Some(Frame::Text("hello".to_owned()))
+        );
+        conn.send_text("hi").await.expect("send text");
+        assert_eq!(
+            conn.recv_frame().await.expect("read close"),
+            Some(Frame::Close)
+        );
+        assert_eq!(conn.recv_frame().await.expect("read eof"), None);
+        drop(conn);
+
+        let mut written = Vec::new();
+        output.read_to_end(&mut written).await.expect("read output");
+        assert_eq!(written, b"\x8a\x04ping\x81\x02hi\x88\x00");
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors() {
+        let fails = |frame: Vec<u8>| async move {
+            let (writer, mut output) = tokio::io::duplex(1024);
+            let mut conn = WebSocketConn::new(BufReader::new(Cursor::new(frame)), writer);
+
+            let result = conn.recv_frame().await;
+            drop(conn);
+
+            let mut written = Vec::new();
+            output.read_to_end(&mut written).await.expect("read output");
+
+            (result.is_err(), written)
+        };
+
+        // Closed with 1002 Protocol Error instead of a pong
+        let close = b"\x88\x02\x03\xea".to_vec();
+        assert_eq!(
+            fails(client_frame(true, 0x9, &[0; 126])).await,
+            (true, close.clone())
+        );
+        assert_eq!(
+            fails(client_frame(false, 0x9, b"ping")).await,
+            (true, close.clone())
+        );
+        assert_eq!(
+            fails(client_frame(true, 0x41, b"hi")).await,
+            (true, close.clone())
+        );
+        assert_eq!(fails(client_frame(true, 0x3, b"hi")).await, (true, close));
+
+        let mut unmasked = client_frame(true, 0x1, b"");
+        unmasked[1] &= 0x7f;
+        unmasked.truncate(2);
+        assert!(fails(unmasked).await.0);
+
+        // A ping at the limit is still answered
+        let (_, written) = fails(client_frame(true, 0x9, &[0; 125])).await;
+        assert_eq!(&written[..2], b"\x8a\x7d");
+    }
+}
@@ -0,0 +1,639 @@
+use http_server_starter_rust::{
+    app,
+    config::ServerConfig,
+    files::{self, StaticConfig},
+    forwarded::TrustProxy,
+    request::Request,
+    response::{self, ResponseLine},
+    router::Router,
+    server::Server,
+    websocket::{Frame, WebSocketConn},
+};
+use pretty_assertions::assert_eq;
+use reqwest::{Response, StatusCode};
+use std::{net::SocketAddr, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+
+/// Starts a server with the challenge routes on an ephemeral port
+async fn spawn_server() -> SocketAddr {
+    spawn_router(app::router()).await
+}
+
+/// Starts a server with `router` on an ephemeral port, it runs until the test finishes
+async fn spawn_router(router: Router) -> SocketAddr {
+    spawn_with_config(router, ServerConfig::default()).await
+}
+
+async fn spawn_with_config(router: Router, config: ServerConfig) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind listener");
+    let addr = listener.local_addr().expect("local addr");
+
+    let server = Server::new(router, config);
+    tokio::spawn(server.run(listener, std::future::pending()));
+
+    addr
+}
+
+async fn get(addr: SocketAddr, path: &str) -> Response {
+    reqwest::get(format!("http://{addr}{path}"))
+        .await
+        .expect("send request")
+}
+
+fn assert_status(response: &Response, status: StatusCode) {
+    assert_eq!(response.status(), status);
+}
+
+fn assert_header(response: &Response, name: &str, value: &str) {
+    let actual = response
+        .headers()
+        .get(name)
+        .map(|value| value.to_str().expect("ascii header"));
+
+    assert_eq!(actual, Some(value), "header {name}");
+}
+
+/// The status code on the first line of a raw `response`
+fn raw_status(response: &str) -> u16 {
+    let line = response.lines().next().unwrap_or_default();
+    let (_, line) = ResponseLine::parse(line).expect("parse response line");
+
+    line.status_code
+}
+
+async fn assert_body_text(response: Response, body: &str) {
+    assert_eq!(response.text().await.expect("read body"), body);
+}
+
+#[tokio::test]
+async fn test_root() {
+    let addr = spawn_server().await;
+
+    let response = get(addr, "/").await;
+    assert_status(&response, StatusCode::OK);
+    assert_body_text(response, "").await;
+}
+
+#[tokio::test]
+async fn test_user_agent() {
+    let addr = spawn_server().await;
+
+    let response = reqwest::Client::new()
+        .get(format!("http://{addr}/user-agent"))
+        .header("User-Agent", "integration/1.0")
+        .send()
+        .await
+        .expect("send request");
+
+    assert_status(&response, StatusCode::OK);
+    assert_header(&response, "content-type", "text/plain");
+    assert_body_text(response, "integration/1.0").await;
+}
+
+#[tokio::test]
+async fn test_echo() {
+    let addr = spawn_server().await;
+
+    let response = get(addr, "/echo/hello").await;
+    assert_status(&response, StatusCode::OK);
+    assert_header(&response, "content-length", "5");
+    assert_body_text(response, "hello").await;
+}
+
+#[tokio::test]
+async fn test_not_found() {
+    let addr = spawn_server().await;
+
+    let response = get(addr, "/does-not-exist").await;
+    assert_status(&response, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_websocket_echo() {
+    let router = Router::new().websocket("/ws", |mut conn: WebSocketConn, _| async move {
+        while let Ok(Some(Frame::Text(text))) = conn.recv_frame().await {
+            let _ = conn.send_text(&text).await;
+        }
+    });
+    let addr = spawn_router(router).await;
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(
+            b"GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+            Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\r\n",
+        )
+        .await
+        .expect("send handshake");
+
+    let mut handshake = Vec::new();
+    while !handshake.ends_with(b"\r\n\r\n") {
+        handshake.push(stream.read_u8().await.expect("read handshake"));
+    }
+
+    let handshake = String::from_utf8(handshake).expect("utf-8 handshake");
+    assert!(handshake.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(handshake.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+    // A masked text frame with the mask set to zeros
+    stream
+        .write_all(b"\x81\x85\x00\x00\x00\x00hello")
+        .await
+        .expect("send frame");
+
+    let mut frame = [0; 7];
+    stream.read_exact(&mut frame).await.expect("read frame");
+    assert_eq!(&frame, b"\x81\x05hello");
+}
+
+#[tokio::test]
+async fn test_pipelining() {
+    let addr = spawn_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(
+            b"GET /echo/one HTTP/1.1\r\nHost: localhost\r\n\r\n\
+            GET /echo/two HTTP/1.1\r\nHost: localhost\r\n\r\n\
+            GET /echo/three HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .expect("send requests");
+
+    let mut responses = String::new();
+    stream
+        .read_to_string(&mut responses)
+        .await
+        .expect("read responses");
+
+    let bodies: Vec<_> = responses
+        .split("HTTP/1.1 200 OK\r\n")
+        .skip(1)
+        .filter_map(|response| response.split_once("\r\n\r\n"))
+        .map(|(_, body)| body)
+        .collect();
+
+    assert_eq!(bodies, ["one", "two", "three"]);
+}
+
+#[tokio::test]
+async fn test_expect_continue() {
+    let router = Router::new().post("/echo", |request: Request, _| async move {
+        response::Response::builder().body(request.body).build()
+    });
+    let addr = spawn_router(router).await;
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\
+            Expect: 100-continue\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .expect("send head");
+
+    let mut interim = [0; 25];
+    stream.read_exact(&mut interim).await.expect("read interim");
+    assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+
+    stream.write_all(b"hello").await.expect("send body");
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("read response");
+    assert_eq!(raw_status(&response), 200);
+    assert!(response.ends_with("\r\n\r\nhello"));
+}
+
+#[tokio::test]
+async fn test_max_header_count() {
+    let addr = spawn_server().await;
+
+    let headers: String = (0..101).map(|i| format!("X-Header-{i}: {i}\r\n")).collect();
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(format!("GET / HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n").as_bytes())
+        .await
+        .expect("send request");
+
+    // The server closes the connection after answering
+    let mut response = String::new();
+    timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("connection closed")
+        .expect("read response");
+    assert_eq!(raw_status(&response), 431);
+}
+
+#[tokio::test]
+async fn test_non_utf8_header() {
+    let addr = spawn_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nX-Name: \xff\r\n\r\n")
+        .await
+        .expect("send request");
+
+    let mut response = String::new();
+    timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("connection closed")
+        .expect("read response");
+    assert_eq!(raw_status(&response), 400);
+}
+
+#[tokio::test]
+async fn test_http_1_0() {
+    let router = Router::new().get("/stream", |_, _| async {
+        response::Response::builder()
+            .chunked_body("streamed".as_bytes())
+            .build()
+    });
+    let addr = spawn_router(router).await;
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(b"GET /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+        .await
+        .expect("send request");
+
+    // Closed after the response even though the client asked to keep it alive
+    let mut response = String::new();
+    timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("connection closed")
+        .expect("read response");
+
+    assert_eq!(raw_status(&response), 200);
+    assert!(response.contains("\r\nConnection: close\r\n"), "{response}");
+    assert!(response.contains("\r\nContent-Length: 8\r\n"), "{response}");
+    assert!(!response.contains("Transfer-Encoding"), "{response}");
+    assert!(response.ends_with("\r\n\r\nstreamed"));
+}
+
+#[tokio::test]
+async fn test_read_body_timeout() {
+    let router = Router::new().post("/upload", |_, _| async {
+        response::Response::empty(response::Status::Created)
+    });
+    let config = ServerConfig {
+        read_body_timeout: Duration::from_millis(100),
+        ..ServerConfig::default()
+    };
+    let addr = spawn_with_config(router, config).await;
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhe")
+        .await
+        .expect("send request");
+
+    let mut response = String::new();
+    timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("connection closed")
+        .expect("read response");
+    assert_eq!(raw_status(&response), 408);
+}
+
+#[tokio::test]
+async fn test_request_id() {
+    let addr = spawn_server().await;
+
+    let response = get(addr, "/").await;
+    let id = response
+        .headers()
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .expect("request id");
+    assert_eq!(id.len(), 36);
+
+    // Only trusted proxies may choose the ID
+    let response = reqwest::Client::new()
+        .get(format!("http://{addr}/"))
+        .header("X-Request-Id", "spoofed")
+        .send()
+        .await
+        .expect("send request");
+    assert_ne!(
+        response.headers().get("x-request-id").expect("request id"),
+        "spoofed"
+    );
+
+    // A trusted proxy's ID is kept even when the request is rejected
+    let config = ServerConfig {
+        trust_proxy: TrustProxy {
+            trusted: vec!["127.0.0.1".parse().expect("cidr")],
+        },
+        ..ServerConfig::default()
+    };
+    let addr = spawn_with_config(app::router(), config).await;
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nX-Request-Id: upstream-1\r\n\r\n")
+        .await
+        .expect("send request");
+
+    let mut response = String::new();
+    timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("connection closed")
+        .expect("read response");
+    assert_eq!(raw_status(&response), 400);
+    assert!(
+        response
+            .to_ascii_lowercase()
+            .contains("\r\nx-request-id: upstream-1\r\n"),
+        "{response}"
+    );
+}
+
+#[tokio::test]
+async fn test_trace_refused() {
+    let addr = spawn_server().await;
+
+    let response = reqwest::Client::new()
+        .request(reqwest::Method::TRACE, format!("http://{addr}/"))
+        .send()
+        .await
+        .expect("send request");
+
+    assert_status(&response, StatusCode::METHOD_NOT_ALLOWED);
+    assert_header(&response, "allow", "GET, HEAD");
+}
+
+#[cfg(not(feature = "proxy"))]
+#[tokio::test]
+async fn test_connect_refused() {
+    let addr = spawn_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream
+        .write_all(b"CONNECT /echo/abc HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .expect("send request");
+
+    let mut response = String::new();
+    timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("connection closed")
+        .expect("read response");
+    assert_eq!(raw_status(&response), 405);
+    assert!(response.contains("\r\nAllow: GET, HEAD\r\n"), "{response}");
+}
+
+#[tokio::test]
+async fn test_server_header() {
+    let addr = spawn_server().await;
+
+    let response = get(addr, "/does-not-exist").await;
+    assert_header(
+        &response,
+        "server",
+        concat!("crsh/", env!("CARGO_PKG_VERSION")),
+    );
+
+    let config = ServerConfig {
+        server_header: None,
+        ..ServerConfig::default()
+    };
+    let addr = spawn_with_config(app::router(), config).await;
+
+    let response = get(addr, "/").await;
+    assert!(response.headers().get("server").is_none());
+}
+
+#[tokio::test]
+async fn test_server_timing() {
+    let addr = spawn_server().await;
+
+    let response = get(addr, "/").await;
+    assert!(response.headers().get("server-timing").is_none());
+
+    let config = ServerConfig {
+        server_timing: true,
+        ..ServerConfig::default()
+    };
+    let addr = spawn_with_config(app::router(), config).await;
+
+    let response = get(addr, "/").await;
+    let timing = response
+        .headers()
+        .get("server-timing")
+        .and_then(|timing| timing.to_str().ok())
+        .expect("server timing");
+
+    let stages: Vec<_> = timing
+        .split(", ")
+        .map(|stage| stage.split_once(";dur=").expect("duration"))
+        .collect();
+    assert_eq!(
+        stages.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+        ["parse", "handler", "serialize"]
+    );
+    for (_, duration) in stages {
+        assert!(duration.parse::<f64>().is_ok(), "{timing}");
+    }
+}
+
+#[tokio::test]
+async fn test_date_header() {
+    let addr = spawn_server().await;
+
+    let response = get(addr, "/").await;
+    let date = response
+        .headers()
+        .get("date")
+        .and_then(|date| date.to_str().ok())
+        .expect("date");
+    assert!(date.ends_with(" GMT"), "{date}");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket() {
+    use http_server_starter_rust::server;
+    use tokio::net::UnixStream;
+
+    let temp = tempfile::tempdir().expect("create dir");
+    let path = temp.path().join("server.sock");
+    let listener = server::bind_unix(&path).expect("bind socket");
+
+    let server = Server::new(app::router(), ServerConfig::default());
+    tokio::spawn(server.run(listener, std::future::pending()));
+
+    let mut stream = UnixStream::connect(&path).await.expect("connect");
+    stream
+        .write_all(b"GET /echo/unix HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .expect("send request");
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("read response");
+    assert_eq!(raw_status(&response), 200);
+    assert!(response.ends_with("\r\n\r\nunix"));
+}
+
+#[tokio::test]
+async fn test_binary_file_round_trip() {
+    let temp = tempfile::tempdir().expect("create dir");
+    let dir = temp.path();
+
+    let addr = spawn_router(files::routes(
+        Router::new(),
+        StaticConfig::new(dir.to_owned()),
+    ))
+    .await;
+
+    // A PNG signature and IHDR chunk start, with bytes that aren't valid UTF-8
+    let png = [
+        0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0x00, 0x00, 0x00, 0x0d, b'I', b'H',
+        b'D', b'R', 0xff, 0xfe, 0x00, 0x80,
+    ];
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{addr}/files/image.png"))
+        .header("Content-Type", "application/octet-stream")
+        .body(png.to_vec())
+        .send()
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::CREATED);
+
+    let response = get(addr, "/files/image.png").await;
+    assert_status(&response, StatusCode::OK);
+    let body = response.bytes().await.expect("read body");
+
+    assert_eq!(&body[..], &png[..]);
+}
+
+#[cfg(feature = "kv")]
+#[tokio::test]
+async fn test_kv_store() {
+    use http_server_starter_rust::kv;
+
+    let addr = spawn_router(kv::routes(Router::new(), kv::Store::default())).await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{addr}{path}");
+
+    assert_status(&get(addr, "/kv/name").await, StatusCode::NOT_FOUND);
+
+    let response = client
+        .put(url("/kv/name"))
+        .body("teevik")
+        .send()
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::CREATED);
+
+    let response = client
+        .put(url("/kv/lang"))
+        .body("rust")
+        .send()
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::CREATED);
+
+    let response = get(addr, "/kv/name").await;
+    assert_status(&response, StatusCode::OK);
+    assert_body_text(response, "teevik").await;
+
+    let response = get(addr, "/kv").await;
+    assert_header(&response, "content-type", "application/json");
+    assert_body_text(response, r#"["lang","name"]"#).await;
+
+    let patch = |key: &str, content_type: &str, body: &'static str| {
+        client
+            .patch(url(&format!("/kv/{key}")))
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+    };
+
+    let response = patch("lang", "application/merge-patch+json", "{}")
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::CONFLICT);
+
+    let response = client
+        .put(url("/kv/config"))
+        .body(r#"{"port":4221,"tls":true}"#)
+        .send()
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::CREATED);
+
+    let response = patch("config", "application/json", "{}")
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let response = patch("config", "application/merge-patch+json", r#"{"tls":null}"#)
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::OK);
+    assert_body_text(response, r#"{"port":4221}"#).await;
+
+    let response = client
+        .delete(url("/kv/name"))
+        .send()
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::NO_CONTENT);
+
+    assert_status(&get(addr, "/kv/name").await, StatusCode::NOT_FOUND);
+
+    let response = client
+        .delete(url("/kv/name"))
+        .send()
+        .await
+        .expect("send request");
+    assert_status(&response, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_max_connections() {
+    let config = ServerConfig {
+        max_connections: 1,
+        ..ServerConfig::default()
+    };
+    let addr = spawn_with_config(app::router(), config).await;
+    let request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+    let mut first = TcpStream::connect(addr).await.expect("connect");
+    first.write_all(request).await.expect("send request");
+
+    let mut buf = [0; 1024];
+    let len = first.read(&mut buf).await.expect("read response");
+    assert_eq!(raw_status(&String::from_utf8_lossy(&buf[..len])), 200);
+
+    // Waits in the backlog while the first connection is kept alive
+    let mut second = TcpStream::connect(addr).await.expect("connect");
+    second.write_all(request).await.expect("send request");
+
+    let waiting = timeout(Duration::from_millis(200), second.read(&mut buf)).await;
+    assert!(waiting.is_err(), "second connection was served");
+
+    drop(first);
+
+    let len = timeout(Duration::from_secs(5), second.read(&mut buf))
+        .await
+        .expect("second connection served")
+        .expect("read response");
+    assert_eq!(raw_status(&String::from_utf8_lossy(&buf[..len])), 200);
+}